@@ -6,7 +6,7 @@ use stardust_xr_wire::{
 	},
 	messenger::MessengerError,
 };
-use std::any::TypeId;
+use std::{any::TypeId, path::PathBuf};
 use thiserror::Error;
 
 pub type Result<T, E = ServerError> = std::result::Result<T, E>;
@@ -27,6 +27,10 @@ pub enum ServerError {
 	DeserializationError(#[from] DeserializationError),
 	#[error("Reader error: {0}")]
 	ReaderError(#[from] ReaderError),
+	#[error("Quota exceeded: clients may only have {limit} {resource}")]
+	QuotaExceeded { resource: &'static str, limit: u64 },
+	#[error("Not allowed to load resource {}", .0.display())]
+	ResourceNotAllowed(PathBuf),
 	#[error("Aspect {} does not exist for node", 0.to_string())]
 	NoAspect(TypeId),
 	#[error("{0}")]
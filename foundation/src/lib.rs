@@ -2,6 +2,7 @@ pub mod delta;
 pub mod error;
 mod id;
 mod method_response;
+pub mod recording;
 pub mod registry;
 pub mod resource;
 pub mod task;
@@ -1,29 +1,45 @@
 use crate::error::{Result, ServerError};
 use serde::Serialize;
 use stardust_xr_wire::{flex::serialize, messenger::MethodResponse, scenegraph::ScenegraphError};
+use std::os::fd::OwnedFd;
 
-pub struct MethodResponseSender(pub(crate) MethodResponse);
+pub struct MethodResponseSender {
+	response: MethodResponse,
+	on_sent: Option<Box<dyn FnOnce() + Send>>,
+}
 impl MethodResponseSender {
+	/// Run `f` once the response has been sent, whether it's a value or an error
+	pub fn on_sent(mut self, f: impl FnOnce() + Send + 'static) -> Self {
+		self.on_sent = Some(Box::new(f));
+		self
+	}
+	fn respond(self, result: Result<(&[u8], Vec<OwnedFd>), ScenegraphError>) {
+		self.response.send(result);
+		if let Some(on_sent) = self.on_sent {
+			on_sent();
+		}
+	}
+
 	pub fn send_err(self, error: ScenegraphError) {
-		self.0.send(Err(error));
+		self.respond(Err(error));
 	}
 	pub fn send<T: Serialize>(self, result: Result<T, ServerError>) {
 		let data = match result {
 			Ok(d) => d,
 			Err(e) => {
-				self.0.send(Err(ScenegraphError::MemberError {
+				self.respond(Err(ScenegraphError::MemberError {
 					error: e.to_string(),
 				}));
 				return;
 			}
 		};
 		let Ok((serialized, fds)) = stardust_xr_wire::flex::serialize(data) else {
-			self.0.send(Err(ScenegraphError::MemberError {
+			self.respond(Err(ScenegraphError::MemberError {
 				error: "Internal: Failed to serialize".to_string(),
 			}));
 			return;
 		};
-		self.0.send(Ok((&serialized, fds)));
+		self.respond(Ok((&serialized, fds)));
 	}
 	pub fn wrap<T: Serialize, F: FnOnce() -> Result<T>>(self, f: F) {
 		self.send(f())
@@ -33,25 +49,28 @@ impl MethodResponseSender {
 			let value = match f.await {
 				Ok(d) => d,
 				Err(e) => {
-					self.0.send(Err(ScenegraphError::MemberError {
+					self.respond(Err(ScenegraphError::MemberError {
 						error: e.to_string(),
 					}));
 					return;
 				}
 			};
 			let Ok((serialized, fds)) = serialize(value) else {
-				self.0.send(Err(ScenegraphError::MemberError {
+				self.respond(Err(ScenegraphError::MemberError {
 					error: "Internal: Failed to serialize".to_string(),
 				}));
 				return;
 			};
-			self.0.send(Ok((&serialized, fds)));
+			self.respond(Ok((&serialized, fds)));
 		});
 	}
 }
 impl From<MethodResponse> for MethodResponseSender {
 	fn from(response: MethodResponse) -> Self {
-		Self(response)
+		Self {
+			response,
+			on_sent: None,
+		}
 	}
 }
 impl std::fmt::Debug for MethodResponseSender {
@@ -0,0 +1,205 @@
+//! Recordings of the messages between the server and a client, written by the server when it's
+//! started with `--record` and played back with `stardust-xr-replay`.
+
+use parking_lot::Mutex;
+use std::{
+	fs::File,
+	io::{self, ErrorKind, Read, Write},
+	path::Path,
+	time::{Duration, Instant},
+};
+
+const MAGIC: &[u8; 8] = b"SXRREC\0\0";
+const FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum RecordedMessageKind {
+	/// Signal from the client to the server
+	Signal = 0,
+	/// Method call from the client to the server
+	Method = 1,
+	/// Signal from the server to the client
+	RemoteSignal = 2,
+}
+impl TryFrom<u8> for RecordedMessageKind {
+	type Error = io::Error;
+	fn try_from(value: u8) -> io::Result<Self> {
+		match value {
+			0 => Ok(RecordedMessageKind::Signal),
+			1 => Ok(RecordedMessageKind::Method),
+			2 => Ok(RecordedMessageKind::RemoteSignal),
+			_ => Err(io::Error::new(
+				ErrorKind::InvalidData,
+				format!("Unknown message kind {value}"),
+			)),
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordedMessage {
+	/// Time since the client connected
+	pub time: Duration,
+	pub kind: RecordedMessageKind,
+	pub node: u64,
+	pub aspect: u64,
+	pub method: u64,
+	/// The serialized arguments
+	pub data: Vec<u8>,
+	/// File descriptors can't be recorded, only how many were sent
+	pub fd_count: u32,
+}
+impl RecordedMessage {
+	fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+		let mut buffer = Vec::with_capacity(45 + self.data.len());
+		buffer.extend_from_slice(&(self.time.as_nanos() as u64).to_le_bytes());
+		buffer.push(self.kind as u8);
+		buffer.extend_from_slice(&self.node.to_le_bytes());
+		buffer.extend_from_slice(&self.aspect.to_le_bytes());
+		buffer.extend_from_slice(&self.method.to_le_bytes());
+		buffer.extend_from_slice(&self.fd_count.to_le_bytes());
+		buffer.extend_from_slice(&(self.data.len() as u64).to_le_bytes());
+		buffer.extend_from_slice(&self.data);
+		// one write per message so a crash can only cut off the last one
+		writer.write_all(&buffer)
+	}
+	fn read_from(reader: &mut impl Read) -> io::Result<Self> {
+		let time = Duration::from_nanos(read_u64(reader)?);
+		let mut kind = [0; 1];
+		reader.read_exact(&mut kind)?;
+		let kind = RecordedMessageKind::try_from(kind[0])?;
+		let node = read_u64(reader)?;
+		let aspect = read_u64(reader)?;
+		let method = read_u64(reader)?;
+		let mut fd_count = [0; 4];
+		reader.read_exact(&mut fd_count)?;
+		let data_len = read_u64(reader)?;
+		let mut data = Vec::new();
+		reader.take(data_len).read_to_end(&mut data)?;
+		if data.len() as u64 != data_len {
+			return Err(ErrorKind::UnexpectedEof.into());
+		}
+		Ok(RecordedMessage {
+			time,
+			kind,
+			node,
+			aspect,
+			method,
+			data,
+			fd_count: u32::from_le_bytes(fd_count),
+		})
+	}
+}
+fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+	let mut bytes = [0; 8];
+	reader.read_exact(&mut bytes)?;
+	Ok(u64::from_le_bytes(bytes))
+}
+
+/// Appends every message of a client to a recording file
+pub struct Recorder {
+	start: Instant,
+	file: Mutex<File>,
+}
+impl Recorder {
+	pub fn create(path: &Path) -> io::Result<Self> {
+		let mut file = File::create(path)?;
+		file.write_all(MAGIC)?;
+		file.write_all(&FORMAT_VERSION.to_le_bytes())?;
+		Ok(Recorder {
+			start: Instant::now(),
+			file: Mutex::new(file),
+		})
+	}
+	pub fn record(
+		&self,
+		kind: RecordedMessageKind,
+		node: u64,
+		aspect: u64,
+		method: u64,
+		data: &[u8],
+		fd_count: usize,
+	) -> io::Result<()> {
+		RecordedMessage {
+			time: self.start.elapsed(),
+			kind,
+			node,
+			aspect,
+			method,
+			data: data.to_vec(),
+			fd_count: fd_count as u32,
+		}
+		.write_to(&mut *self.file.lock())
+	}
+}
+
+/// Read all messages of a recording. If the server stopped in the middle of writing a message,
+/// the messages before it are still returned.
+pub fn read_recording(mut reader: impl Read) -> io::Result<Vec<RecordedMessage>> {
+	let mut magic = [0; 8];
+	reader.read_exact(&mut magic)?;
+	if &magic != MAGIC {
+		return Err(io::Error::new(
+			ErrorKind::InvalidData,
+			"Not a stardust recording",
+		));
+	}
+	let mut version = [0; 4];
+	reader.read_exact(&mut version)?;
+	let version = u32::from_le_bytes(version);
+	if version != FORMAT_VERSION {
+		return Err(io::Error::new(
+			ErrorKind::InvalidData,
+			format!("Unsupported recording format version {version}"),
+		));
+	}
+
+	let mut messages = Vec::new();
+	loop {
+		match RecordedMessage::read_from(&mut reader) {
+			Ok(message) => messages.push(message),
+			Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(messages),
+			Err(e) => return Err(e),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn recording_round_trips() {
+		let messages = [
+			RecordedMessage {
+				time: Duration::from_millis(5),
+				kind: RecordedMessageKind::Signal,
+				node: 0,
+				aspect: 1,
+				method: 2,
+				data: vec![1, 2, 3],
+				fd_count: 0,
+			},
+			RecordedMessage {
+				time: Duration::from_millis(12),
+				kind: RecordedMessageKind::RemoteSignal,
+				node: 256,
+				aspect: 3,
+				method: 4,
+				data: Vec::new(),
+				fd_count: 1,
+			},
+		];
+		let mut recording = MAGIC.to_vec();
+		recording.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+		for message in &messages {
+			message.write_to(&mut recording).unwrap();
+		}
+		assert_eq!(read_recording(recording.as_slice()).unwrap(), messages);
+
+		// a message cut off by a crash is dropped
+		recording.truncate(recording.len() - 3);
+		assert_eq!(read_recording(recording.as_slice()).unwrap(), messages[..1]);
+	}
+}
@@ -11,6 +11,11 @@ static THEMES: LazyLock<Vec<PathBuf>> = LazyLock::new(|| {
 		.unwrap_or_default()
 });
 
+/// Directories from `STARDUST_THEMES` that are searched before a client's own prefixes
+pub fn themes() -> &'static [PathBuf] {
+	&THEMES
+}
+
 fn has_extension(path: &Path, extensions: &[&OsStr]) -> bool {
 	if let Some(path_extension) = path.extension() {
 		extensions.contains(&path_extension)
@@ -0,0 +1,162 @@
+use clap::Parser;
+use color_eyre::eyre::{Result, WrapErr, eyre};
+use stardust_xr_server_foundation::recording::{RecordedMessageKind, read_recording};
+use stardust_xr_wire::{
+	messenger::{self, MethodResponse},
+	scenegraph::{Scenegraph, ScenegraphError},
+};
+use std::{fs::File, io::BufReader, os::fd::OwnedFd, path::PathBuf, time::Duration};
+use tokio::{net::UnixStream, task::JoinSet, time::Instant};
+
+/// Play back a client recording made with `stardust-xr-server --record` against a running server
+#[derive(Debug, Parser)]
+#[clap(author, version, about, long_about = None)]
+struct CliArgs {
+	/// The `.rec` file to play back
+	recording: PathBuf,
+
+	/// Socket of the server to connect to, defaults to the one of `STARDUST_INSTANCE`
+	#[clap(long)]
+	socket: Option<PathBuf>,
+
+	/// Send every message right away instead of with the original timing
+	#[clap(long, action)]
+	fast: bool,
+
+	/// Stay connected after the recording is over until killed
+	#[clap(long, action)]
+	hold: bool,
+}
+
+/// The recording only has what the client sent, so anything from the server is ignored
+struct ReplayScenegraph;
+impl Scenegraph for ReplayScenegraph {
+	fn send_signal(
+		&self,
+		_node_id: u64,
+		_aspect_id: u64,
+		_method: u64,
+		_data: &[u8],
+		_fds: Vec<OwnedFd>,
+	) -> Result<(), ScenegraphError> {
+		Ok(())
+	}
+	fn execute_method(
+		&self,
+		_node_id: u64,
+		_aspect_id: u64,
+		_method: u64,
+		_data: &[u8],
+		_fds: Vec<OwnedFd>,
+		response: MethodResponse,
+	) {
+		response.send(Err(ScenegraphError::MemberError {
+			error: "Replayed clients can't answer method calls".to_string(),
+		}));
+	}
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+	color_eyre::install()?;
+	let args = CliArgs::parse();
+
+	let recording = File::open(&args.recording)
+		.wrap_err_with(|| format!("Couldn't open {}", args.recording.display()))?;
+	let messages = read_recording(BufReader::new(recording))
+		.wrap_err_with(|| format!("Couldn't read {}", args.recording.display()))?;
+
+	let socket_path = match args.socket {
+		Some(socket_path) => socket_path,
+		None => default_socket_path()?,
+	};
+	let connection = UnixStream::connect(&socket_path)
+		.await
+		.wrap_err_with(|| format!("Couldn't connect to {}", socket_path.display()))?;
+	let (mut messenger_tx, mut messenger_rx) = messenger::create(connection);
+	let handle = messenger_tx.handle();
+	tokio::spawn(async move { while messenger_tx.flush().await.is_ok() {} });
+	let disconnected = tokio::spawn(async move {
+		loop {
+			if let Err(e) = messenger_rx.dispatch(&ReplayScenegraph).await {
+				return e;
+			}
+		}
+	});
+
+	let replay = async {
+		let start = Instant::now();
+		let mut pending_methods = JoinSet::new();
+		let mut server_signals = 0;
+		for message in messages {
+			if message.kind == RecordedMessageKind::RemoteSignal {
+				server_signals += 1;
+				continue;
+			}
+			if !args.fast {
+				tokio::time::sleep_until(start + message.time).await;
+			}
+			if message.fd_count > 0 {
+				eprintln!(
+					"Message {}/{}/{} was sent with {} file descriptors, replaying it without them",
+					message.node, message.aspect, message.method, message.fd_count
+				);
+			}
+			match message.kind {
+				RecordedMessageKind::Signal => {
+					if let Err(e) = handle.signal(
+						message.node,
+						message.aspect,
+						message.method,
+						&message.data,
+						Vec::new(),
+					) {
+						eprintln!("Couldn't send signal: {e}");
+					}
+				}
+				RecordedMessageKind::Method => {
+					let handle = handle.clone();
+					pending_methods.spawn(async move {
+						let result = handle
+							.method(
+								message.node,
+								message.aspect,
+								message.method,
+								&message.data,
+								Vec::new(),
+							)
+							.await;
+						match result {
+							Ok(Ok(_)) => (),
+							Ok(Err(e)) => eprintln!(
+								"Method {}/{}/{} failed: {e:?}",
+								message.node, message.aspect, message.method
+							),
+							Err(e) => eprintln!("Couldn't call method: {e}"),
+						}
+					});
+				}
+				RecordedMessageKind::RemoteSignal => unreachable!(),
+			}
+		}
+		while pending_methods.join_next().await.is_some() {}
+		// give the last signals a moment to get flushed
+		tokio::time::sleep(Duration::from_millis(100)).await;
+		println!("Replay finished, skipped {server_signals} signals the server sent");
+		if args.hold {
+			std::future::pending::<()>().await;
+		}
+	};
+
+	tokio::select! {
+		_ = replay => Ok(()),
+		e = disconnected => Err(eyre!("Server disconnected: {e:?}")),
+	}
+}
+
+fn default_socket_path() -> Result<PathBuf> {
+	let runtime_dir = std::env::var_os("XDG_RUNTIME_DIR")
+		.ok_or_else(|| eyre!("XDG_RUNTIME_DIR isn't set, pass --socket"))?;
+	let instance = std::env::var("STARDUST_INSTANCE").unwrap_or_else(|_| "stardust-0".to_string());
+	Ok(PathBuf::from(runtime_dir).join(instance))
+}
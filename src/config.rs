@@ -0,0 +1,141 @@
+use crate::{
+	core::{
+		metrics::MetricsConfig, permissions::PermissionConfig, quota::ClientLimits,
+		resource_policy::ResourceConfig, watchdog::WatchdogConfig,
+	},
+	session::SessionConfig,
+};
+use directories::ProjectDirs;
+use openxr::EnvironmentBlendMode;
+use serde::Deserialize;
+use std::sync::OnceLock;
+use tracing::{error, info};
+
+static SERVER_CONFIG: OnceLock<ServerConfig> = OnceLock::new();
+
+/// Server settings loaded from `server.toml` in the stardust config directory
+/// (usually `~/.config/stardust/server.toml`). Every field is optional.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+	/// Defaults for the display flags, the command line flags take priority
+	pub display: DisplayConfig,
+	/// Defaults for the input flags, the command line flags take priority
+	pub input: InputConfig,
+	/// Resource limits applied to every client
+	pub limits: ClientLimits,
+	/// Which interface methods clients may use
+	pub permissions: PermissionConfig,
+	/// Which files clients may make the server load
+	pub resources: ResourceConfig,
+	/// Detection and handling of unresponsive clients
+	pub watchdog: WatchdogConfig,
+	/// Per-client traffic metrics
+	pub metrics: MetricsConfig,
+	/// Saving and restoring sessions
+	pub session: SessionConfig,
+}
+impl ServerConfig {
+	/// Load the config file, falling back to the defaults if it doesn't exist or can't be parsed.
+	/// Only the first call has any effect.
+	pub fn load(project_dirs: Option<&ProjectDirs>) -> &'static ServerConfig {
+		SERVER_CONFIG.get_or_init(|| {
+			let Some(project_dirs) = project_dirs else {
+				return ServerConfig::default();
+			};
+			let path = project_dirs.config_dir().join("server.toml");
+			let Ok(config_string) = std::fs::read_to_string(&path) else {
+				return ServerConfig::default();
+			};
+			match toml::from_str(&config_string) {
+				Ok(config) => {
+					info!(path = ?path.display(), "Loaded server config");
+					config
+				}
+				Err(e) => {
+					error!(path = ?path.display(), "Invalid server config, using defaults: {e}");
+					ServerConfig::default()
+				}
+			}
+		})
+	}
+	/// The loaded config, or the defaults if it hasn't been loaded.
+	pub fn get() -> &'static ServerConfig {
+		SERVER_CONFIG.get_or_init(ServerConfig::default)
+	}
+}
+
+/// The `[display]` section of `server.toml`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DisplayConfig {
+	/// Same as `--force-flatscreen`
+	pub force_flatscreen: bool,
+	/// Same as `--xr-only`
+	pub xr_only: bool,
+	/// Same as `--headless`
+	pub headless: bool,
+	/// Same as `--spectator`
+	pub spectator: bool,
+	/// Same as `--transparent-flatscreen`
+	pub transparent_flatscreen: bool,
+	/// Same as `--overlay`
+	pub overlay_priority: Option<u32>,
+	/// Same as `--disable-pipelined-rendering`
+	pub disable_pipelined_rendering: bool,
+	/// Brightness of the ambient light lighting every model
+	pub ambient_light_brightness: f32,
+	/// How far below the camera the floor is when there is no tracked stage, in meters
+	pub flatscreen_floor_height: f32,
+	/// OpenXR blend modes to use, the first one the runtime supports is picked
+	pub blend_modes: Vec<BlendMode>,
+}
+impl Default for DisplayConfig {
+	fn default() -> Self {
+		DisplayConfig {
+			force_flatscreen: false,
+			xr_only: false,
+			headless: false,
+			spectator: false,
+			transparent_flatscreen: false,
+			overlay_priority: None,
+			disable_pipelined_rendering: false,
+			ambient_light_brightness: 1000.0,
+			flatscreen_floor_height: 1.65,
+			blend_modes: vec![
+				BlendMode::AlphaBlend,
+				BlendMode::Additive,
+				BlendMode::Opaque,
+			],
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlendMode {
+	Opaque,
+	Additive,
+	AlphaBlend,
+}
+impl From<BlendMode> for EnvironmentBlendMode {
+	fn from(blend_mode: BlendMode) -> Self {
+		match blend_mode {
+			BlendMode::Opaque => EnvironmentBlendMode::OPAQUE,
+			BlendMode::Additive => EnvironmentBlendMode::ADDITIVE,
+			BlendMode::AlphaBlend => EnvironmentBlendMode::ALPHA_BLEND,
+		}
+	}
+}
+
+/// The `[input]` section of `server.toml`.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct InputConfig {
+	/// Same as `--disable-controllers`
+	pub disable_controllers: bool,
+	/// Same as `--disable-hands`
+	pub disable_hands: bool,
+	/// Same as `--transparent-hands`
+	pub transparent_hands: bool,
+}
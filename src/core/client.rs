@@ -1,36 +1,70 @@
 use super::{
 	client_state::{CLIENT_STATES, ClientStateParsed},
+	metrics::ClientMetrics,
+	permissions::{Permission, sandbox_app_id},
+	quota::{ClientLimits, Quota, QuotaKind, QuotaViolationPolicy},
+	resource_policy::canonicalize_within,
 	scenegraph::Scenegraph,
+	transaction::Transaction,
 };
 use crate::{
-	core::{Id, registry::OwnedRegistry, task},
+	config::ServerConfig,
+	core::{
+		Id,
+		error::ServerError,
+		recording::{RecordedMessageKind, Recorder},
+		registry::OwnedRegistry,
+		resource, task,
+	},
 	nodes::{
-		Node, audio, camera, drawable::{self, dmatex::ImportedDmatex}, fields, input, items, root::{ClientState, Root}, spatial
+		Node, alias::AliasList, audio, camera, drawable::{self, dmatex::ImportedDmatex}, fields, input, items, root::{ClientState, Root}, spatial
 	},
 };
 use color_eyre::eyre::{Result, eyre};
 use dashmap::DashMap;
 use global_counter::primitive::exact::CounterU32;
 use parking_lot::Mutex;
-use rustc_hash::FxHashMap;
-use stardust_xr_wire::messenger::{self, MessageSenderHandle};
+use rustc_hash::{FxHashMap, FxHashSet};
+use stardust_xr_server_foundation::{bail, ensure};
+use stardust_xr_wire::{
+	messenger::{self, MessageSenderHandle},
+	values::ResourceID,
+};
 use std::{
+	ffi::OsStr,
 	fmt::Debug,
 	fs,
 	iter::FromIterator,
-	path::PathBuf,
-	sync::{Arc, LazyLock, OnceLock},
-	time::Instant,
+	path::{Path, PathBuf},
+	sync::{
+		Arc, LazyLock, OnceLock, Weak,
+		atomic::{AtomicBool, AtomicU64, Ordering},
+	},
+	time::{Duration, Instant},
 };
-use tokio::{net::UnixStream, sync::watch, task::JoinHandle};
-use tracing::info;
+use tokio::{
+	net::UnixStream,
+	sync::{Notify, watch},
+	task::JoinHandle,
+};
+use tracing::{info, warn};
 
 pub static CLIENTS: OwnedRegistry<Client> = OwnedRegistry::new();
+static CLIENT_ID_COUNTER: AtomicU64 = AtomicU64::new(0);
+/// Set with `--record`, every client's messages get recorded to a file in here
+pub static RECORDING_DIR: OnceLock<PathBuf> = OnceLock::new();
+/// Notified every time a client disconnects
+pub static CLIENT_DISCONNECTED: LazyLock<Notify> = LazyLock::new(Notify::new);
+/// Like `CLIENT_DISCONNECTED`, but keeps a permit for the autosave task so a disconnect while
+/// it's saving isn't missed
+pub static AUTOSAVE_DISCONNECTED: LazyLock<Notify> = LazyLock::new(Notify::new);
+static SHUTDOWN_ACKNOWLEDGED: LazyLock<Notify> = LazyLock::new(Notify::new);
 
 static INTERNAL_CLIENT_MESSAGE_TIMES: LazyLock<(watch::Sender<Instant>, watch::Receiver<Instant>)> =
 	LazyLock::new(|| watch::channel(Instant::now()));
 pub static INTERNAL_CLIENT: LazyLock<Arc<Client>> = LazyLock::new(|| {
 	CLIENTS.add(Client {
+		id: CLIENT_ID_COUNTER.fetch_add(1, Ordering::Relaxed),
 		pid: None,
 		// env: None,
 		exe: None,
@@ -45,10 +79,63 @@ pub static INTERNAL_CLIENT: LazyLock<Arc<Client>> = LazyLock::new(|| {
 		scenegraph: Default::default(),
 		root: OnceLock::new(),
 		base_resource_prefixes: Default::default(),
-		state: OnceLock::default(),
+		state: Mutex::new(None),
+		state_token: Mutex::new(None),
 		dmatexes: DashMap::new(),
+		quota: Quota::new(ClientLimits::UNLIMITED),
+		permissions: Mutex::new(Permission::ALL.into_iter().collect()),
+		hung_since: Mutex::new(None),
+		shutdown_acknowledged: AtomicBool::new(false),
+		metrics: ClientMetrics::default(),
+		transaction: Transaction::default(),
+		spatial_ref_aliases: AliasList::default(),
+		recorder: None,
 	})
 });
+/// Tell every connected client that the server is shutting down, then wait until all of them
+/// have acknowledged it or disconnected, but no longer than `deadline`.
+pub async fn shutdown_clients(deadline: Duration) {
+	let clients = CLIENTS
+		.get_vec()
+		.into_iter()
+		.filter(|client| client.message_sender_handle.is_some())
+		.filter(|client| {
+			let Some(root) = client.root.get() else {
+				return false;
+			};
+			root.send_shutdown(deadline)
+				.inspect_err(|e| warn!(?client, "Couldn't announce the shutdown: {e}"))
+				.is_ok()
+		})
+		.map(|client| Arc::downgrade(&client))
+		.collect::<Vec<_>>();
+	let remaining = || {
+		clients
+			.iter()
+			.filter_map(Weak::upgrade)
+			.filter(|client| !client.shutdown_done())
+			.count()
+	};
+	let all_done = async {
+		loop {
+			let disconnected = CLIENT_DISCONNECTED.notified();
+			let acknowledged = SHUTDOWN_ACKNOWLEDGED.notified();
+			if remaining() == 0 {
+				return;
+			}
+			tokio::select! {
+				_ = disconnected => (),
+				_ = acknowledged => (),
+			}
+		}
+	};
+	if tokio::time::timeout(deadline, all_done).await.is_err() {
+		warn!(
+			clients = remaining(),
+			"Clients didn't finish shutting down in time"
+		);
+	}
+}
 pub fn tick_internal_client() {
 	let _ = INTERNAL_CLIENT_MESSAGE_TIMES.0.send(Instant::now());
 }
@@ -61,12 +148,17 @@ pub fn get_env(pid: i32) -> Result<FxHashMap<String, String>, std::io::Error> {
 			.map(|(k, v)| (k.to_string(), v.to_string())),
 	))
 }
-pub fn state(env: &FxHashMap<String, String>) -> Option<Arc<ClientStateParsed>> {
-	let token = env.get("STARDUST_STARTUP_TOKEN")?;
+/// The startup token from a client's environment, for clients that don't present it themselves
+pub fn startup_token(env: &FxHashMap<String, String>) -> Option<String> {
+	env.get("STARDUST_STARTUP_TOKEN").cloned()
+}
+pub fn state(token: &str) -> Option<Arc<ClientStateParsed>> {
 	CLIENT_STATES.get(token).as_deref().cloned()
 }
 
 pub struct Client {
+	/// Unique for the lifetime of the server, unlike the pid
+	pub id: u64,
 	pub pid: Option<i32>,
 	// env: Option<FxHashMap<String, String>>,
 	exe: Option<PathBuf>,
@@ -80,31 +172,62 @@ pub struct Client {
 	pub scenegraph: Arc<Scenegraph>,
 	pub root: OnceLock<Arc<Root>>,
 	pub base_resource_prefixes: Mutex<Vec<PathBuf>>,
-	pub state: OnceLock<ClientState>,
+	pub state: Mutex<Option<ClientState>>,
+	/// The startup token `state` was restored from, if any
+	state_token: Mutex<Option<String>>,
 	pub dmatexes: DashMap<Id, Arc<ImportedDmatex>>,
+	pub quota: Quota,
+	/// The interface methods this client may use beyond the basics
+	pub permissions: Mutex<FxHashSet<Permission>>,
+	/// Set by the watchdog while the client is unresponsive
+	pub hung_since: Mutex<Option<Instant>>,
+	shutdown_acknowledged: AtomicBool,
+	pub metrics: ClientMetrics,
+	pub transaction: Transaction,
+	/// Aliases of other clients' spatials handed out by `get_parent` and `get_children`, so
+	/// asking twice gives the same ID
+	pub spatial_ref_aliases: AliasList,
+	recorder: Option<Recorder>,
 }
 impl Client {
 	pub fn from_connection(connection: UnixStream) -> Result<Arc<Self>> {
 		let pid = connection.peer_cred().ok().and_then(|c| c.pid());
 		let env = pid.and_then(|pid| get_env(pid).ok());
 		let exe = pid.and_then(|pid| fs::read_link(format!("/proc/{pid}/exe")).ok());
+		let app_id = pid.and_then(sandbox_app_id);
 		info!(
 			pid,
 			exe = exe
 				.as_ref()
 				.and_then(|exe| exe.to_str().map(|s| s.to_string())),
+			app_id,
 			"New client connected"
 		);
 
 		let (mut messenger_tx, mut messenger_rx) = messenger::create(connection);
 		let scenegraph = Arc::new(Scenegraph::default());
-		let state = env
-			.as_ref()
-			.and_then(state)
-			.unwrap_or_else(|| Arc::new(ClientStateParsed::default()));
+		let startup_token = env.as_ref().and_then(startup_token);
+		let saved_state = startup_token.as_deref().and_then(state);
+		let state_token = saved_state.as_ref().and(startup_token);
+		let state = saved_state.unwrap_or_else(|| Arc::new(ClientStateParsed::default()));
+
+		let id = CLIENT_ID_COUNTER.fetch_add(1, Ordering::Relaxed);
+		let recorder = RECORDING_DIR.get().and_then(|recording_dir| {
+			let exe_name = exe
+				.as_ref()
+				.and_then(|exe| exe.file_name())
+				.map(|exe| exe.to_string_lossy().into_owned())
+				.unwrap_or_else(|| "unknown".to_string());
+			let path = recording_dir.join(format!("{id}-{exe_name}.rec"));
+			Recorder::create(&path)
+				.inspect(|_| info!(path = ?path.display(), "Recording client"))
+				.inspect_err(|e| warn!(path = ?path.display(), "Couldn't record client: {e}"))
+				.ok()
+		});
 
 		let (message_time_tx, message_last_received) = watch::channel(Instant::now());
 		let client = CLIENTS.add(Client {
+			id,
 			pid,
 			// env,
 			exe: exe.clone(),
@@ -119,8 +242,21 @@ impl Client {
 			scenegraph: scenegraph.clone(),
 			root: OnceLock::new(),
 			base_resource_prefixes: Default::default(),
-			state: OnceLock::default(),
+			state: Mutex::new(None),
+			state_token: Mutex::new(None),
 			dmatexes: DashMap::new(),
+			quota: Quota::new(ServerConfig::get().limits.clone()),
+			permissions: Mutex::new(
+				ServerConfig::get()
+					.permissions
+					.for_client(app_id.as_deref(), exe.as_deref()),
+			),
+			hung_since: Mutex::new(None),
+			shutdown_acknowledged: AtomicBool::new(false),
+			metrics: ClientMetrics::default(),
+			transaction: Transaction::default(),
+			spatial_ref_aliases: AliasList::default(),
+			recorder,
 		});
 		let _ = client.scenegraph.client.set(Arc::downgrade(&client));
 		let _ = client.root.set(Root::create(&client, state.root)?);
@@ -132,7 +268,8 @@ impl Client {
 		camera::create_interface(&client)?;
 		items::panel::create_interface(&client)?;
 
-		let _ = client.state.set(state.apply_to(&client));
+		*client.state.lock() = Some(state.apply_to(&client)?);
+		*client.state_token.lock() = state_token;
 
 		let pid_printable = pid
 			.map(|pid| pid.to_string())
@@ -171,6 +308,7 @@ impl Client {
 							if let Err(e) = messenger_tx.flush().await {
 								client.disconnect(Err(e.into()));
 							}
+							client.metrics.flushed();
 						}
 					}
 				},
@@ -192,11 +330,55 @@ impl Client {
 		*cmdline_split.get_mut(0).unwrap() = exe.to_str()?.to_string();
 		Some(cmdline_split)
 	}
+	/// Write a message to this client's recording if it's being recorded
+	pub fn record(
+		&self,
+		kind: RecordedMessageKind,
+		node: u64,
+		aspect: u64,
+		method: u64,
+		data: &[u8],
+		fd_count: usize,
+	) {
+		if let Some(recorder) = &self.recorder
+			&& let Err(e) = recorder.record(kind, node, aspect, method, data, fd_count)
+		{
+			warn!(client = ?self, "Couldn't record message: {e}");
+		}
+	}
+
+	pub fn exe(&self) -> Option<&Path> {
+		self.exe.as_deref()
+	}
 	pub fn get_cwd(&self) -> Option<PathBuf> {
 		let pid = self.pid?;
 		let cwd_proc_path = format!("/proc/{pid}/cwd");
 		std::fs::read_link(cwd_proc_path).ok()
 	}
+	/// Restore the state saved under a startup token the client presented over the protocol.
+	/// Presenting the token it was already restored from (e.g. through its environment) just
+	/// returns the current state.
+	pub fn restore_state(self: &Arc<Self>, token: &str) -> Result<ClientState, ServerError> {
+		let mut state_token = self.state_token.lock();
+		if let Some(restored_token) = state_token.as_deref() {
+			ensure!(
+				restored_token == token,
+				"Client state was already restored from another startup token"
+			);
+			let Some(state) = self.state.lock().clone() else {
+				bail!("Client state isn't set up yet");
+			};
+			return Ok(state);
+		}
+		let Some(saved_state) = state(token) else {
+			bail!("Invalid startup token");
+		};
+		let restored_state = saved_state.apply_to(self)?;
+		*self.state.lock() = Some(restored_state.clone());
+		*state_token = Some(token.to_string());
+		info!(client = ?self, "Restored client state from a presented startup token");
+		Ok(restored_state)
+	}
 	pub async fn save_state(&self) -> Option<ClientStateParsed> {
 		println!("start save state");
 		let internal = self.root.get()?.save_state().await.ok()?;
@@ -215,6 +397,102 @@ impl Client {
 			.ok_or_else(|| eyre!("{} not found", name))
 	}
 
+	/// Check that `usage` is within this client's limit for `kind`, applying the violation
+	/// policy if it isn't.
+	pub fn check_quota(&self, kind: QuotaKind, usage: u64) -> Result<(), ServerError> {
+		let limit = self.quota.limits.limit(kind);
+		if usage <= limit {
+			return Ok(());
+		}
+		warn!(
+			pid = self.pid,
+			exe = ?self.exe,
+			%kind,
+			limit,
+			"Client went over its quota"
+		);
+		if self.quota.limits.on_violation == QuotaViolationPolicy::Disconnect {
+			self.disconnect(Err(eyre!("Exceeded quota of {limit} {kind}")));
+		}
+		Err(ServerError::QuotaExceeded {
+			resource: kind.resource_name(),
+			limit,
+		})
+	}
+
+	/// The directories the resource config lets this client load files from, `None` if it may
+	/// load any file. Base prefixes aren't included, they're checked when the client sets them.
+	pub fn allowed_resource_dirs(&self) -> Option<Vec<PathBuf>> {
+		// the internal client is the server itself
+		if self.message_sender_handle.is_none() {
+			return None;
+		}
+		let config = &ServerConfig::get().resources;
+		let client_config = self.exe().and_then(|exe| config.for_exe(exe));
+		if client_config.is_some_and(|client_config| client_config.unrestricted) {
+			return None;
+		}
+		Some(
+			resource::themes()
+				.iter()
+				.chain(&config.allowed_dirs)
+				.chain(client_config.into_iter().flat_map(|c| &c.allowed_dirs))
+				.cloned()
+				.collect(),
+		)
+	}
+
+	/// Find the file for a resource, `None` if it doesn't exist. Fails if it's outside of the
+	/// client's base prefixes and the directories the resource config lets it load from,
+	/// otherwise the path returned has its symlinks resolved so it can't be swapped out after the
+	/// check.
+	pub fn get_resource_file(
+		&self,
+		resource_id: &ResourceID,
+		extensions: &[&OsStr],
+	) -> Result<Option<PathBuf>, ServerError> {
+		let base_prefixes = self.base_resource_prefixes.lock().clone();
+		let Some(path) = resource::get_resource_file(resource_id, base_prefixes.iter(), extensions)
+		else {
+			return Ok(None);
+		};
+		let Some(allowed_dirs) = self.allowed_resource_dirs() else {
+			return Ok(Some(path));
+		};
+		let Some(canonical_path) =
+			canonicalize_within(&path, allowed_dirs.iter().chain(&base_prefixes))
+		else {
+			warn!(
+				pid = self.pid,
+				exe = ?self.exe,
+				path = ?path.display(),
+				"Client tried to load a resource outside of its allowed directories"
+			);
+			return Err(ServerError::ResourceNotAllowed(path));
+		};
+		Ok(Some(canonical_path))
+	}
+	pub fn has_permission(&self, permission: Permission) -> bool {
+		self.permissions.lock().contains(&permission)
+	}
+	pub fn dmatex_bytes(&self) -> u64 {
+		self.dmatexes.iter().map(|tex| tex.byte_size()).sum()
+	}
+	/// Check the dmatex quotas for importing `byte_size` bytes as `id`, which replaces any
+	/// dmatex already imported with that ID
+	pub fn check_dmatex_quota(&self, id: Id, byte_size: u64) -> Result<(), ServerError> {
+		let replaced_bytes = self.dmatexes.get(&id).map(|tex| tex.byte_size());
+		if replaced_bytes.is_none() {
+			self.check_quota(QuotaKind::Dmatexes, self.dmatexes.len() as u64 + 1)?;
+		}
+		self.check_quota(
+			QuotaKind::DmatexBytes,
+			self.dmatex_bytes()
+				.saturating_sub(replaced_bytes.unwrap_or(0))
+				+ byte_size,
+		)
+	}
+
 	pub fn unresponsive(&self) -> bool {
 		let time_since_last_message = self.message_last_received.borrow().elapsed();
 		time_since_last_message.as_millis() > 500
@@ -229,11 +507,23 @@ impl Client {
 			flush_join_handle.abort();
 		}
 		CLIENTS.remove(self);
+		CLIENT_DISCONNECTED.notify_waiters();
+		AUTOSAVE_DISCONNECTED.notify_one();
+	}
+
+	/// The client sent the `acknowledge_shutdown` signal, so it's done with everything it wanted to do before the server shuts down
+	pub fn acknowledge_shutdown(&self) {
+		self.shutdown_acknowledged.store(true, Ordering::Relaxed);
+		SHUTDOWN_ACKNOWLEDGED.notify_waiters();
+	}
+	fn shutdown_done(&self) -> bool {
+		self.disconnect_status.get().is_some() || self.shutdown_acknowledged.load(Ordering::Relaxed)
 	}
 }
 impl Debug for Client {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
 		f.debug_struct("Client")
+			.field("id", &self.id)
 			.field("pid", &self.pid)
 			.field("exe", &self.exe)
 			.field("dispatch_join_handle", &self.dispatch_join_handle)
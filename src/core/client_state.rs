@@ -1,6 +1,7 @@
 use super::client::{Client, get_env};
 use crate::{
-	core::Id,
+	config::ServerConfig,
+	core::{Id, error::Result},
 	nodes::{Node, root::ClientState, spatial::Spatial},
 };
 use dashmap::DashMap;
@@ -8,14 +9,42 @@ use glam::Mat4;
 use rustc_hash::FxHashMap;
 use serde::{Deserialize, Serialize};
 use std::{
+	fs::File,
+	io::Write,
 	path::{Path, PathBuf},
 	process::Command,
 	sync::{Arc, LazyLock},
 };
+use thiserror::Error;
 
 pub static CLIENT_STATES: LazyLock<DashMap<String, Arc<ClientStateParsed>>> =
 	LazyLock::new(Default::default);
 
+/// Bump this and add a migration to `MIGRATIONS` whenever the layout of the state files changes.
+pub const CLIENT_STATE_FORMAT_VERSION: u32 = 1;
+/// `MIGRATIONS[n]` upgrades the TOML of a version `n` state file to version `n + 1`.
+const MIGRATIONS: [fn(&mut toml::Table) -> Result<(), String>;
+	CLIENT_STATE_FORMAT_VERSION as usize] = [
+	// version 0 files are from before the version field existed, otherwise they're the same
+	|_| Ok(()),
+];
+
+#[derive(Error, Debug)]
+pub enum ClientStateError {
+	#[error("Couldn't read the state: {0}")]
+	Io(#[from] std::io::Error),
+	#[error("Invalid state file: {0}")]
+	Toml(#[from] toml::de::Error),
+	#[error("format_version must be an integer")]
+	InvalidVersion,
+	#[error(
+		"Format version {0} isn't supported by this server (it supports up to {CLIENT_STATE_FORMAT_VERSION})"
+	)]
+	UnsupportedVersion(i64),
+	#[error("Couldn't migrate from format version {version}: {reason}")]
+	Migration { version: u32, reason: String },
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct LaunchInfo {
 	pub cmdline: Vec<String>,
@@ -24,16 +53,20 @@ pub struct LaunchInfo {
 }
 impl LaunchInfo {
 	fn from_client(client: &Client) -> Option<Self> {
+		let mut env = get_env(client.pid?).ok()?;
+		ServerConfig::get().session.saved_env.filter(&mut env);
 		Some(LaunchInfo {
 			cmdline: client.get_cmdline()?,
 			cwd: client.get_cwd()?,
-			env: get_env(client.pid?).ok()?,
+			env,
 		})
 	}
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ClientStateParsed {
+	/// Always `CLIENT_STATE_FORMAT_VERSION` once loaded, older files get migrated
+	pub format_version: u32,
 	pub launch_info: Option<LaunchInfo>,
 	#[serde(skip)]
 	pub data: Option<Vec<u8>>,
@@ -43,6 +76,7 @@ pub struct ClientStateParsed {
 impl ClientStateParsed {
 	pub fn from_deserialized(client: &Client, state: ClientState) -> Self {
 		ClientStateParsed {
+			format_version: CLIENT_STATE_FORMAT_VERSION,
 			launch_info: LaunchInfo::from_client(client),
 			data: state.data,
 			root: Self::spatial_transform(client, state.root).unwrap_or_default(),
@@ -64,13 +98,36 @@ impl ClientStateParsed {
 		CLIENT_STATES.insert(token.clone(), Arc::new(self));
 		token
 	}
-	pub fn from_file(file: &Path) -> Option<Self> {
-		let file_string = std::fs::read_to_string(file).ok()?;
-		let mut client_state: Self = toml::from_str(&file_string).ok()?;
+	pub fn from_file(file: &Path) -> Result<Self, ClientStateError> {
+		let file_string = std::fs::read_to_string(file)?;
+		let mut client_state = Self::from_toml(&file_string)?;
 		client_state.data = std::fs::read(file.with_extension("bin")).ok();
-		Some(client_state)
+		Ok(client_state)
 	}
-	pub fn to_file(&self, directory: &Path) {
+	/// Parse the metadata of a state file, migrating it from older format versions
+	pub fn from_toml(toml_string: &str) -> Result<Self, ClientStateError> {
+		let mut table: toml::Table = toml::from_str(toml_string)?;
+		let version = match table.get("format_version") {
+			None => 0,
+			Some(toml::Value::Integer(version)) => *version,
+			Some(_) => return Err(ClientStateError::InvalidVersion),
+		};
+		let mut version = u32::try_from(version)
+			.ok()
+			.filter(|v| *v <= CLIENT_STATE_FORMAT_VERSION)
+			.ok_or(ClientStateError::UnsupportedVersion(version))?;
+		while version < CLIENT_STATE_FORMAT_VERSION {
+			MIGRATIONS[version as usize](&mut table)
+				.map_err(|reason| ClientStateError::Migration { version, reason })?;
+			version += 1;
+		}
+		table.insert(
+			"format_version".to_string(),
+			toml::Value::Integer(CLIENT_STATE_FORMAT_VERSION.into()),
+		);
+		Ok(toml::Value::Table(table).try_into()?)
+	}
+	pub fn to_file(&self, directory: &Path) -> std::io::Result<()> {
 		let app_name = self
 			.launch_info
 			.as_ref()
@@ -80,29 +137,32 @@ impl ClientStateParsed {
 		let state_metadata_path = state_file_prefix.with_extension("toml");
 		let state_data_path = state_file_prefix.with_extension("bin");
 
-		std::fs::write(state_metadata_path, toml::to_string(&self).unwrap()).unwrap();
+		// the metadata goes last so restoring never sees a state without its data
 		if let Some(data) = self.data.as_deref() {
-			std::fs::write(state_data_path, data).unwrap();
+			write_atomic(&state_data_path, data)?;
 		}
+		let metadata = toml::to_string(&self).map_err(std::io::Error::other)?;
+		write_atomic(&state_metadata_path, metadata)
 	}
 
-	pub fn apply_to(&self, client: &Arc<Client>) -> ClientState {
+	/// Fails if the client can't have the nodes for the spatial anchors, e.g. because of its quota
+	pub fn apply_to(&self, client: &Arc<Client>) -> Result<ClientState> {
 		if let Some(root) = client.root.get() {
 			root.set_transform(self.root)
 		}
-		ClientState {
+		Ok(ClientState {
 			data: self.data.clone(),
 			root: Id(0),
 			spatial_anchors: self
 				.spatial_anchors
 				.iter()
 				.map(|(k, v)| {
-					let node = Node::generate(client, true).add_to_scenegraph().unwrap();
+					let node = Node::generate(client, true).add_to_scenegraph()?;
 					Spatial::add_to(&node, None, *v);
-					(k.clone(), node.get_id())
+					Ok((k.clone(), node.get_id()))
 				})
-				.collect(),
-		}
+				.collect::<Result<_>>()?,
+		})
 	}
 	pub fn launch_command(self) -> Option<Command> {
 		let launch_info = self.launch_info.as_ref()?;
@@ -110,14 +170,41 @@ impl ClientStateParsed {
 		let mut command = Command::new(cmdline.next()?);
 		command.args(cmdline);
 		command.current_dir(&launch_info.cwd);
-		command.envs(launch_info.env.iter());
+		// sessions saved before the filter existed still have everything in them
+		let saved_env = &ServerConfig::get().session.saved_env;
+		command.envs(
+			launch_info
+				.env
+				.iter()
+				.filter(|(var, _)| saved_env.allows(var)),
+		);
 		command.env("STARDUST_STARTUP_TOKEN", self.token());
 		Some(command)
 	}
 }
+
+/// Write to a temporary file first so a crash never leaves a half written file at `path`
+pub fn write_atomic(path: &Path, contents: impl AsRef<[u8]>) -> std::io::Result<()> {
+	// next to the full file name, `with_extension` would give `x.toml` and `x.bin` the same one
+	let mut temp_path = path.as_os_str().to_owned();
+	temp_path.push(".tmp");
+	let temp_path = PathBuf::from(temp_path);
+	let mut file = File::create(&temp_path)?;
+	file.write_all(contents.as_ref())?;
+	// the data has to be on disk before the rename is, or a crash could leave an empty file
+	file.sync_all()?;
+	drop(file);
+	std::fs::rename(temp_path, path)?;
+	if let Some(directory) = path.parent() {
+		File::open(directory)?.sync_all()?;
+	}
+	Ok(())
+}
+
 impl Default for ClientStateParsed {
 	fn default() -> Self {
 		Self {
+			format_version: CLIENT_STATE_FORMAT_VERSION,
 			launch_info: None,
 			data: None,
 			root: Mat4::IDENTITY,
@@ -125,3 +212,54 @@ impl Default for ClientStateParsed {
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const UNVERSIONED_STATE: &str = r#"
+root = [1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.5, 0.0, 1.0]
+
+[launch_info]
+cmdline = ["/usr/bin/flatland", "--verbose"]
+cwd = "/home/user"
+
+[launch_info.env]
+HOME = "/home/user"
+
+[spatial_anchors]
+"#;
+
+	#[test]
+	fn unversioned_state_is_migrated() {
+		let state = ClientStateParsed::from_toml(UNVERSIONED_STATE).unwrap();
+		assert_eq!(state.format_version, CLIENT_STATE_FORMAT_VERSION);
+		assert_eq!(state.root.w_axis.y, 1.5);
+		let launch_info = state.launch_info.unwrap();
+		assert_eq!(launch_info.cmdline, ["/usr/bin/flatland", "--verbose"]);
+		assert_eq!(launch_info.env.get("HOME").unwrap(), "/home/user");
+	}
+
+	#[test]
+	fn newer_state_is_rejected() {
+		let state = format!("format_version = 999\n{UNVERSIONED_STATE}");
+		assert!(matches!(
+			ClientStateParsed::from_toml(&state),
+			Err(ClientStateError::UnsupportedVersion(999))
+		));
+	}
+
+	#[test]
+	fn saved_state_round_trips() {
+		let mut state = ClientStateParsed::default();
+		state.spatial_anchors.insert(
+			"anchor".to_string(),
+			Mat4::from_translation([1.0, 2.0, 3.0].into()),
+		);
+		let toml_string = toml::to_string(&state).unwrap();
+		let loaded = ClientStateParsed::from_toml(&toml_string).unwrap();
+		assert_eq!(loaded.format_version, CLIENT_STATE_FORMAT_VERSION);
+		assert_eq!(loaded.root, state.root);
+		assert_eq!(loaded.spatial_anchors, state.spatial_anchors);
+	}
+}
@@ -0,0 +1,406 @@
+use super::{
+	client::{CLIENTS, Client},
+	client_state::write_atomic,
+};
+use crate::{config::ServerConfig, objects::object_path};
+use parking_lot::Mutex;
+use rustc_hash::FxHashMap;
+use serde::Deserialize;
+use std::{
+	fmt::Write,
+	path::PathBuf,
+	sync::{
+		Arc,
+		atomic::{AtomicU64, Ordering},
+	},
+	time::Duration,
+};
+use tracing::warn;
+use zbus::{Connection, fdo, interface};
+
+/// The `[metrics]` section of `server.toml`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MetricsConfig {
+	/// Periodically write every client's metrics to this file in the Prometheus text format,
+	/// e.g. for node_exporter's textfile collector
+	pub prometheus_file: Option<PathBuf>,
+	/// How often the Prometheus file is rewritten, in seconds
+	pub write_interval_secs: f32,
+}
+impl Default for MetricsConfig {
+	fn default() -> Self {
+		MetricsConfig {
+			prometheus_file: None,
+			write_interval_secs: 10.0,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum MessageKind {
+	/// Signal from the client to the server
+	ReceivedSignal,
+	/// Method call from the client to the server
+	ReceivedMethod,
+	/// Signal from the server to the client
+	SentSignal,
+	/// Method call from the server to the client
+	SentMethod,
+}
+impl MessageKind {
+	pub fn name(self) -> &'static str {
+		match self {
+			MessageKind::ReceivedSignal => "received_signal",
+			MessageKind::ReceivedMethod => "received_method",
+			MessageKind::SentSignal => "sent_signal",
+			MessageKind::SentMethod => "sent_method",
+		}
+	}
+	fn received(self) -> bool {
+		matches!(
+			self,
+			MessageKind::ReceivedSignal | MessageKind::ReceivedMethod
+		)
+	}
+}
+
+/// Totals for one member of one aspect
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct MemberMetrics {
+	pub count: u64,
+	pub bytes: u64,
+	/// Time spent handling received messages before the dispatch loop could move on
+	pub handle_time: Duration,
+	/// Time between a method being called and its response being sent
+	pub latency: Duration,
+	pub max_latency: Duration,
+}
+
+/// Aspect and member ID of the entry counting every member past `MAX_MEMBERS`
+pub const OTHER_MEMBERS: u64 = u64::MAX;
+/// Members are counted before they're known to exist, so a client sending made up IDs can't be
+/// allowed to grow the map forever
+const MAX_MEMBERS: usize = 1024;
+
+/// Traffic counters for a single client
+#[derive(Default)]
+pub struct ClientMetrics {
+	members: Mutex<FxHashMap<(MessageKind, u64, u64), MemberMetrics>>,
+	queued: AtomicU64,
+	flushed: AtomicU64,
+}
+impl ClientMetrics {
+	fn update(
+		&self,
+		kind: MessageKind,
+		aspect: u64,
+		member: u64,
+		f: impl FnOnce(&mut MemberMetrics),
+	) {
+		let mut members = self.members.lock();
+		let key = if members.len() >= MAX_MEMBERS && !members.contains_key(&(kind, aspect, member))
+		{
+			(kind, OTHER_MEMBERS, OTHER_MEMBERS)
+		} else {
+			(kind, aspect, member)
+		};
+		f(members.entry(key).or_default());
+	}
+	pub fn message(&self, kind: MessageKind, aspect: u64, member: u64, bytes: usize) {
+		if !kind.received() {
+			self.queued.fetch_add(1, Ordering::Relaxed);
+		}
+		self.update(kind, aspect, member, |metrics| {
+			metrics.count += 1;
+			metrics.bytes += bytes as u64;
+		});
+	}
+	pub fn handled(&self, kind: MessageKind, aspect: u64, member: u64, time: Duration) {
+		self.update(kind, aspect, member, |metrics| metrics.handle_time += time);
+	}
+	pub fn responded(&self, kind: MessageKind, aspect: u64, member: u64, latency: Duration) {
+		if kind.received() {
+			// the response goes out through the same queue
+			self.queued.fetch_add(1, Ordering::Relaxed);
+		}
+		self.update(kind, aspect, member, |metrics| {
+			metrics.latency += latency;
+			metrics.max_latency = metrics.max_latency.max(latency);
+		});
+	}
+	/// Called by the flush loop every time it has written out the queue
+	pub fn flushed(&self) {
+		self.flushed
+			.store(self.queued.load(Ordering::Relaxed), Ordering::Relaxed);
+	}
+	/// Roughly how many messages are waiting to be written to the client's socket
+	pub fn pending_outgoing(&self) -> u64 {
+		self.queued
+			.load(Ordering::Relaxed)
+			.saturating_sub(self.flushed.load(Ordering::Relaxed))
+	}
+
+	pub fn members(&self) -> Vec<((MessageKind, u64, u64), MemberMetrics)> {
+		let mut members = self
+			.members
+			.lock()
+			.iter()
+			.map(|(key, metrics)| (*key, *metrics))
+			.collect::<Vec<_>>();
+		members.sort_unstable_by_key(|(key, _)| *key);
+		members
+	}
+	/// Messages and bytes as (received, sent, bytes received, bytes sent)
+	pub fn totals(&self) -> (u64, u64, u64, u64) {
+		self.members
+			.lock()
+			.iter()
+			.fold((0, 0, 0, 0), |mut totals, ((kind, _, _), metrics)| {
+				if kind.received() {
+					totals.0 += metrics.count;
+					totals.2 += metrics.bytes;
+				} else {
+					totals.1 += metrics.count;
+					totals.3 += metrics.bytes;
+				}
+				totals
+			})
+	}
+}
+
+/// Expose the metrics of every client over DBus and write them to the Prometheus file if one is
+/// configured.
+pub async fn serve_metrics(connection: Connection) {
+	if let Err(e) = connection
+		.object_server()
+		.at(object_path("Metrics"), Metrics)
+		.await
+	{
+		warn!("Couldn't add the metrics to dbus: {e}");
+	}
+	let config = ServerConfig::get().metrics.clone();
+	let Some(path) = config.prometheus_file else {
+		return;
+	};
+	let write_interval = Duration::try_from_secs_f32(config.write_interval_secs.max(0.1))
+		.unwrap_or_else(|e| {
+			let default = MetricsConfig::default().write_interval_secs;
+			warn!(
+				"Invalid metrics.write_interval_secs of {}, using {default}: {e}",
+				config.write_interval_secs
+			);
+			Duration::from_secs_f32(default)
+		});
+	let mut interval = tokio::time::interval(write_interval);
+	loop {
+		interval.tick().await;
+		let clients = connected_clients();
+		let text = prometheus_text(
+			clients
+				.iter()
+				.map(|client| (client.id, exe_name(client), &client.metrics)),
+		);
+		// scrapers never see a half written file
+		let result = tokio::task::spawn_blocking({
+			let path = path.clone();
+			move || write_atomic(&path, text)
+		})
+		.await
+		.unwrap_or_else(|e| Err(std::io::Error::other(e)));
+		if let Err(e) = result {
+			warn!(path = ?path.display(), "Couldn't write the metrics: {e}");
+		}
+	}
+}
+
+fn connected_clients() -> Vec<Arc<Client>> {
+	CLIENTS
+		.get_vec()
+		.into_iter()
+		// the internal client never touches a socket
+		.filter(|client| client.message_sender_handle.is_some())
+		.collect()
+}
+fn exe_name(client: &Client) -> String {
+	client
+		.exe()
+		.and_then(|exe| exe.file_name())
+		.map(|exe| exe.to_string_lossy().into_owned())
+		.unwrap_or_default()
+}
+
+fn prometheus_text<'a>(clients: impl Iterator<Item = (u64, String, &'a ClientMetrics)>) -> String {
+	let clients = clients
+		.map(|(id, exe, metrics)| {
+			let labels = format!("client=\"{id}\",exe=\"{}\"", escape_label(&exe));
+			(labels, metrics.members(), metrics.pending_outgoing())
+		})
+		.collect::<Vec<_>>();
+
+	let mut text = String::new();
+	let mut write_member_metric = |name: &str, help: &str, value: fn(&MemberMetrics) -> String| {
+		let _ = writeln!(text, "# HELP {name} {help}");
+		let _ = writeln!(text, "# TYPE {name} counter");
+		for (labels, members, _) in &clients {
+			for ((kind, aspect, member), metrics) in members {
+				let id_label = |id: u64| match id {
+					OTHER_MEMBERS => "other".to_string(),
+					id => id.to_string(),
+				};
+				let _ = writeln!(
+					text,
+					"{name}{{{labels},kind=\"{}\",aspect=\"{}\",member=\"{}\"}} {}",
+					kind.name(),
+					id_label(*aspect),
+					id_label(*member),
+					value(metrics)
+				);
+			}
+		}
+	};
+	write_member_metric(
+		"stardust_client_messages_total",
+		"Messages between the server and a client",
+		|m| m.count.to_string(),
+	);
+	write_member_metric(
+		"stardust_client_message_bytes_total",
+		"Serialized size of the messages between the server and a client",
+		|m| m.bytes.to_string(),
+	);
+	write_member_metric(
+		"stardust_client_handle_seconds_total",
+		"Time the server spent handling messages from a client",
+		|m| m.handle_time.as_secs_f64().to_string(),
+	);
+	write_member_metric(
+		"stardust_client_method_latency_seconds_total",
+		"Time between method calls and their responses",
+		|m| m.latency.as_secs_f64().to_string(),
+	);
+	let name = "stardust_client_pending_outgoing_messages";
+	let _ = writeln!(
+		text,
+		"# HELP {name} Messages waiting to be written to a client's socket"
+	);
+	let _ = writeln!(text, "# TYPE {name} gauge");
+	for (labels, _, pending) in &clients {
+		let _ = writeln!(text, "{name}{{{labels}}} {pending}");
+	}
+	text
+}
+fn escape_label(value: &str) -> String {
+	value
+		.replace('\\', "\\\\")
+		.replace('"', "\\\"")
+		.replace('\n', "\\n")
+}
+
+/// (client ID, pid or -1, executable name, messages received, messages sent, bytes received,
+/// bytes sent, messages waiting to be sent)
+type ClientSummary = (u64, i32, String, u64, u64, u64, u64, u64);
+/// (kind, aspect ID, member opcode, count, bytes, handling time in ms, total method latency in
+/// ms, highest method latency in ms)
+type MemberSummary = (String, u64, u64, u64, u64, f64, f64, f64);
+
+/// Per-client traffic, for finding out which client is slowing everything down
+struct Metrics;
+#[interface(name = "org.stardustxr.Metrics")]
+impl Metrics {
+	/// A summary of every connected client
+	fn clients(&self) -> Vec<ClientSummary> {
+		connected_clients()
+			.into_iter()
+			.map(|client| {
+				let (received, sent, bytes_received, bytes_sent) = client.metrics.totals();
+				(
+					client.id,
+					client.pid.unwrap_or(-1),
+					exe_name(&client),
+					received,
+					sent,
+					bytes_received,
+					bytes_sent,
+					client.metrics.pending_outgoing(),
+				)
+			})
+			.collect()
+	}
+
+	/// Totals per aspect member of a client
+	fn members(&self, client_id: u64) -> fdo::Result<Vec<MemberSummary>> {
+		let client = connected_clients()
+			.into_iter()
+			.find(|client| client.id == client_id)
+			.ok_or_else(|| fdo::Error::InvalidArgs(format!("No client with ID {client_id}")))?;
+		Ok(client
+			.metrics
+			.members()
+			.into_iter()
+			.map(|((kind, aspect, member), metrics)| {
+				(
+					kind.name().to_string(),
+					aspect,
+					member,
+					metrics.count,
+					metrics.bytes,
+					metrics.handle_time.as_secs_f64() * 1000.0,
+					metrics.latency.as_secs_f64() * 1000.0,
+					metrics.max_latency.as_secs_f64() * 1000.0,
+				)
+			})
+			.collect())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn prometheus_format() {
+		let metrics = ClientMetrics::default();
+		metrics.message(MessageKind::ReceivedMethod, 1, 2, 16);
+		metrics.handled(MessageKind::ReceivedMethod, 1, 2, Duration::from_millis(1));
+		metrics.responded(MessageKind::ReceivedMethod, 1, 2, Duration::from_millis(3));
+		metrics.message(MessageKind::SentSignal, 3, 4, 8);
+		assert_eq!(metrics.totals(), (1, 1, 16, 8));
+		assert_eq!(metrics.pending_outgoing(), 2);
+		metrics.flushed();
+		assert_eq!(metrics.pending_outgoing(), 0);
+
+		let text = prometheus_text([(7, "a \"b\"".to_string(), &metrics)].into_iter());
+		assert!(text.contains(
+			"stardust_client_messages_total{client=\"7\",exe=\"a \\\"b\\\"\",kind=\"received_method\",aspect=\"1\",member=\"2\"} 1\n"
+		));
+		assert!(text.contains(
+			"stardust_client_method_latency_seconds_total{client=\"7\",exe=\"a \\\"b\\\"\",kind=\"received_method\",aspect=\"1\",member=\"2\"} 0.003\n"
+		));
+		assert!(text.contains(
+			"stardust_client_pending_outgoing_messages{client=\"7\",exe=\"a \\\"b\\\"\"} 0\n"
+		));
+	}
+
+	#[test]
+	fn member_cap() {
+		let metrics = ClientMetrics::default();
+		for member in 0..MAX_MEMBERS as u64 + 10 {
+			metrics.message(MessageKind::ReceivedSignal, 1, member, 1);
+		}
+		let members = metrics.members();
+		assert_eq!(members.len(), MAX_MEMBERS + 1);
+		assert_eq!(
+			members.last().unwrap(),
+			&(
+				(MessageKind::ReceivedSignal, OTHER_MEMBERS, OTHER_MEMBERS),
+				MemberMetrics {
+					count: 10,
+					bytes: 10,
+					..Default::default()
+				}
+			)
+		);
+		assert_eq!(metrics.totals().0, MAX_MEMBERS as u64 + 10);
+	}
+}
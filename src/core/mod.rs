@@ -1,6 +1,14 @@
 pub mod client;
 pub mod client_state;
+pub mod metrics;
+pub mod permissions;
+pub mod quota;
+pub mod resource_policy;
 pub mod scenegraph;
+#[cfg(test)]
+pub mod test_client;
+pub mod transaction;
 pub mod vulkano_data;
+pub mod watchdog;
 
 pub use stardust_xr_server_foundation::*;
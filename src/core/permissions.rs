@@ -0,0 +1,182 @@
+use crate::nodes::{Node, camera, drawable, fields, input, items::panel, spatial};
+use rustc_hash::{FxHashMap, FxHashSet};
+use serde::Deserialize;
+use std::{fmt::Display, path::Path};
+
+/// Interface methods that let a client affect the whole scene or other clients
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Permission {
+	/// `set_sky_tex` and `set_sky_light`
+	Sky,
+	/// `register_panel_item_ui`, which hands the client every other client's panels
+	ItemUi,
+	/// `create_input_method`, which can send input to other clients
+	InputMethod,
+	/// `import_spatial_ref` and `import_field_ref`
+	Import,
+	/// `create_camera`, which can see everything in the scene
+	Camera,
+}
+impl Permission {
+	pub const ALL: [Permission; 5] = [
+		Permission::Sky,
+		Permission::ItemUi,
+		Permission::InputMethod,
+		Permission::Import,
+		Permission::Camera,
+	];
+
+	/// The permission needed to run a member of an aspect on `node`, if any
+	pub fn required(node: &Node, aspect_id: u64, member: u64) -> Option<Permission> {
+		// every interface uses aspect ID 0, so the node's interface type tells them apart
+		if aspect_id != 0 {
+			return None;
+		}
+		if node.get_aspect::<drawable::Interface>().is_ok()
+			&& (member == drawable::INTERFACE_SET_SKY_TEX_SERVER_OPCODE
+				|| member == drawable::INTERFACE_SET_SKY_LIGHT_SERVER_OPCODE)
+		{
+			return Some(Permission::Sky);
+		}
+		if node.get_aspect::<panel::Interface>().is_ok()
+			&& member == panel::INTERFACE_REGISTER_PANEL_ITEM_UI_SERVER_OPCODE
+		{
+			return Some(Permission::ItemUi);
+		}
+		if node.get_aspect::<input::Interface>().is_ok()
+			&& member == input::INTERFACE_CREATE_INPUT_METHOD_SERVER_OPCODE
+		{
+			return Some(Permission::InputMethod);
+		}
+		if (node.get_aspect::<spatial::Interface>().is_ok()
+			&& member == spatial::INTERFACE_IMPORT_SPATIAL_REF_SERVER_OPCODE)
+			|| (node.get_aspect::<fields::Interface>().is_ok()
+				&& member == fields::INTERFACE_IMPORT_FIELD_REF_SERVER_OPCODE)
+		{
+			return Some(Permission::Import);
+		}
+		if node.get_aspect::<camera::Interface>().is_ok()
+			&& member == camera::INTERFACE_CREATE_CAMERA_SERVER_OPCODE
+		{
+			return Some(Permission::Camera);
+		}
+		None
+	}
+}
+impl Display for Permission {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str(match self {
+			Permission::Sky => "sky",
+			Permission::ItemUi => "item_ui",
+			Permission::InputMethod => "input_method",
+			Permission::Import => "import",
+			Permission::Camera => "camera",
+		})
+	}
+}
+
+/// The `[permissions]` section of `server.toml`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PermissionConfig {
+	/// Permissions of clients without an entry in `clients`
+	pub default: Vec<Permission>,
+	/// Permissions of specific clients, replacing `default`. Keyed by flatpak app ID for
+	/// sandboxed clients and by full canonical executable path for everything else. Executable
+	/// names aren't accepted since any client could copy or rename itself to one.
+	pub clients: FxHashMap<String, Vec<Permission>>,
+}
+impl Default for PermissionConfig {
+	fn default() -> Self {
+		PermissionConfig {
+			default: Permission::ALL.to_vec(),
+			clients: FxHashMap::default(),
+		}
+	}
+}
+impl PermissionConfig {
+	pub fn for_client(&self, app_id: Option<&str>, exe: Option<&Path>) -> FxHashSet<Permission> {
+		// a sandboxed executable's path is inside the sandbox, which the app controls
+		let key = match app_id {
+			Some(app_id) => Some(app_id),
+			None => exe.and_then(|exe| exe.to_str()),
+		};
+		key.and_then(|key| self.clients.get(key))
+			.unwrap_or(&self.default)
+			.iter()
+			.copied()
+			.collect()
+	}
+}
+
+/// The app ID of a flatpak'd process, which unlike its environment it can't fake
+pub fn sandbox_app_id(pid: i32) -> Option<String> {
+	let info = std::fs::read_to_string(format!("/proc/{pid}/root/.flatpak-info")).ok()?;
+	parse_flatpak_app_id(&info)
+}
+fn parse_flatpak_app_id(info: &str) -> Option<String> {
+	info.lines()
+		.map(str::trim)
+		.skip_while(|line| *line != "[Application]")
+		.skip(1)
+		.take_while(|line| !line.starts_with('['))
+		.find_map(|line| line.strip_prefix("name="))
+		.map(|name| name.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn client_permissions() {
+		let config: PermissionConfig = toml::from_str(
+			r#"
+			default = ["import"]
+			[clients]
+			"org.stardustxr.Flatland" = ["item_ui", "input_method"]
+			"/usr/bin/black-hole" = []
+			"flatland" = ["camera"]
+			"#,
+		)
+		.unwrap();
+		let flatland = config.for_client(
+			Some("org.stardustxr.Flatland"),
+			Some(Path::new("/app/bin/flatland")),
+		);
+		assert_eq!(
+			flatland,
+			[Permission::ItemUi, Permission::InputMethod]
+				.into_iter()
+				.collect()
+		);
+		assert!(
+			config
+				.for_client(None, Some(Path::new("/usr/bin/black-hole")))
+				.is_empty()
+		);
+		assert_eq!(
+			config.for_client(None, Some(Path::new("/usr/bin/other"))),
+			[Permission::Import].into_iter().collect()
+		);
+		assert_eq!(
+			config.for_client(None, Some(Path::new("/tmp/flatland"))),
+			[Permission::Import].into_iter().collect()
+		);
+		assert_eq!(
+			config.for_client(
+				Some("org.example.App"),
+				Some(Path::new("/usr/bin/black-hole"))
+			),
+			[Permission::Import].into_iter().collect()
+		);
+
+		let info = "[Application]\nname=org.stardustxr.Flatland\nruntime=runtime/x\n\n[Instance]\nname=wrong\n";
+		assert_eq!(
+			parse_flatpak_app_id(info).as_deref(),
+			Some("org.stardustxr.Flatland")
+		);
+		assert_eq!(parse_flatpak_app_id("[Instance]\nname=wrong\n"), None);
+	}
+}
@@ -0,0 +1,140 @@
+use parking_lot::Mutex;
+use serde::Deserialize;
+use std::{
+	fmt::Display,
+	sync::atomic::{AtomicUsize, Ordering},
+	time::{Duration, Instant},
+};
+
+/// Per-client resource limits, the `[limits]` section of `server.toml`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClientLimits {
+	/// Maximum number of nodes in a client's scenegraph, including aliases
+	pub max_nodes: u64,
+	/// Maximum number of aliases a client may hold
+	pub max_aliases: u64,
+	/// Maximum number of dmatexes a client may have imported at once
+	pub max_dmatexes: u64,
+	/// Maximum total size of the dmatexes a client may have imported at once
+	pub max_dmatex_bytes: u64,
+	/// Maximum number of signals and method calls a client may send each second
+	pub max_messages_per_second: u64,
+	/// Maximum number of signals a client may have held back in transactions at once, each of
+	/// them keeping its data and file descriptors until the next frame
+	pub max_transaction_signals: u64,
+	/// What to do with a client that goes over any limit
+	pub on_violation: QuotaViolationPolicy,
+}
+impl ClientLimits {
+	pub const UNLIMITED: Self = ClientLimits {
+		max_nodes: u64::MAX,
+		max_aliases: u64::MAX,
+		max_dmatexes: u64::MAX,
+		max_dmatex_bytes: u64::MAX,
+		max_messages_per_second: u64::MAX,
+		max_transaction_signals: u64::MAX,
+		on_violation: QuotaViolationPolicy::Reject,
+	};
+
+	pub fn limit(&self, kind: QuotaKind) -> u64 {
+		match kind {
+			QuotaKind::Nodes => self.max_nodes,
+			QuotaKind::Aliases => self.max_aliases,
+			QuotaKind::Dmatexes => self.max_dmatexes,
+			QuotaKind::DmatexBytes => self.max_dmatex_bytes,
+			QuotaKind::MessageRate => self.max_messages_per_second,
+			QuotaKind::TransactionSignals => self.max_transaction_signals,
+		}
+	}
+}
+impl Default for ClientLimits {
+	fn default() -> Self {
+		ClientLimits {
+			max_nodes: 100_000,
+			max_aliases: 100_000,
+			max_dmatexes: 1024,
+			max_dmatex_bytes: 4 * 1024 * 1024 * 1024,
+			max_messages_per_second: 20_000,
+			max_transaction_signals: 20_000,
+			on_violation: QuotaViolationPolicy::Reject,
+		}
+	}
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuotaViolationPolicy {
+	/// Fail the offending request and keep the client connected
+	#[default]
+	Reject,
+	/// Fail the offending request and disconnect the client
+	Disconnect,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuotaKind {
+	Nodes,
+	Aliases,
+	Dmatexes,
+	DmatexBytes,
+	MessageRate,
+	TransactionSignals,
+}
+impl QuotaKind {
+	pub fn resource_name(self) -> &'static str {
+		match self {
+			QuotaKind::Nodes => "nodes",
+			QuotaKind::Aliases => "aliases",
+			QuotaKind::Dmatexes => "dmatexes",
+			QuotaKind::DmatexBytes => "bytes of dmatexes",
+			QuotaKind::MessageRate => "messages per second",
+			QuotaKind::TransactionSignals => "signals in transactions",
+		}
+	}
+}
+impl Display for QuotaKind {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str(self.resource_name())
+	}
+}
+
+/// Tracks the usage that can't be read straight off the client.
+pub struct Quota {
+	pub limits: ClientLimits,
+	aliases: AtomicUsize,
+	message_window: Mutex<(Instant, u64)>,
+}
+impl Quota {
+	pub fn new(limits: ClientLimits) -> Self {
+		Quota {
+			limits,
+			aliases: AtomicUsize::new(0),
+			message_window: Mutex::new((Instant::now(), 0)),
+		}
+	}
+
+	pub fn alias_count(&self) -> u64 {
+		self.aliases.load(Ordering::Relaxed) as u64
+	}
+	pub fn alias_added(&self) {
+		self.aliases.fetch_add(1, Ordering::Relaxed);
+	}
+	pub fn alias_removed(&self) {
+		let _ = self
+			.aliases
+			.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |v| v.checked_sub(1));
+	}
+
+	/// Count a message towards the current one second window, returning how many have been
+	/// received in it so far.
+	pub fn count_message(&self) -> u64 {
+		let mut window = self.message_window.lock();
+		let now = Instant::now();
+		if now.duration_since(window.0) >= Duration::from_secs(1) {
+			*window = (now, 0);
+		}
+		window.1 += 1;
+		window.1
+	}
+}
@@ -0,0 +1,109 @@
+use rustc_hash::FxHashMap;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+/// Which files clients may make the server load, the `[resources]` section of `server.toml`.
+/// Clients can always load from the theme directories and their own base prefixes, as long as
+/// those prefixes pass [`widens_access`].
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ResourceConfig {
+	/// Directories every client may load files from
+	pub allowed_dirs: Vec<PathBuf>,
+	/// Overrides for specific clients, keyed by full canonical executable path. Executable names
+	/// aren't accepted since any client could copy or rename itself to one.
+	pub clients: FxHashMap<String, ClientResourceConfig>,
+}
+impl ResourceConfig {
+	pub fn for_exe(&self, exe: &Path) -> Option<&ClientResourceConfig> {
+		self.clients.get(exe.to_str()?)
+	}
+}
+
+/// An entry under `[resources.clients]`
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClientResourceConfig {
+	/// More directories this client may load files from
+	pub allowed_dirs: Vec<PathBuf>,
+	/// Let this client load any file
+	pub unrestricted: bool,
+}
+
+/// Resolve symlinks and `..` in `path` and return it if the result is inside any of `dirs`.
+/// Use the returned path from then on, the original one could be pointed somewhere else.
+pub fn canonicalize_within<'a>(
+	path: &Path,
+	dirs: impl IntoIterator<Item = &'a PathBuf>,
+) -> Option<PathBuf> {
+	let path = path.canonicalize().ok()?;
+	dirs.into_iter()
+		.filter_map(|dir| dir.canonicalize().ok())
+		.any(|dir| path.starts_with(dir))
+		.then_some(path)
+}
+
+/// Whether letting a client load files from the canonical directory `dir` would expose more than
+/// its resources: the filesystem root, the home directory or anything above it, and hidden
+/// directories in the home directory, which is where credentials and config live.
+pub fn widens_access(dir: &Path, home: Option<&Path>) -> bool {
+	if dir.parent().is_none() {
+		return true;
+	}
+	let Some(home) = home else {
+		return false;
+	};
+	if home.starts_with(dir) {
+		return true;
+	}
+	dir.strip_prefix(home).is_ok_and(|relative| {
+		relative
+			.components()
+			.any(|component| component.as_os_str().to_string_lossy().starts_with('.'))
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn escaping_allowed_dirs() {
+		let root = std::env::temp_dir().join(format!("stardust-resources-{}", std::process::id()));
+		let allowed = root.join("allowed");
+		let _ = std::fs::remove_dir_all(&root);
+		std::fs::create_dir_all(&allowed).unwrap();
+		std::fs::write(allowed.join("model.glb"), []).unwrap();
+		std::fs::write(root.join("secret.glb"), []).unwrap();
+		std::os::unix::fs::symlink(root.join("secret.glb"), allowed.join("link.glb")).unwrap();
+		let allowed_dirs = [allowed.clone()];
+
+		assert_eq!(
+			canonicalize_within(&allowed.join("./model.glb"), &allowed_dirs),
+			Some(allowed.canonicalize().unwrap().join("model.glb"))
+		);
+		assert!(canonicalize_within(&root.join("secret.glb"), &allowed_dirs).is_none());
+		assert!(canonicalize_within(&allowed.join("../secret.glb"), &allowed_dirs).is_none());
+		assert!(canonicalize_within(&allowed.join("link.glb"), &allowed_dirs).is_none());
+		assert!(canonicalize_within(&allowed.join("missing.glb"), &allowed_dirs).is_none());
+
+		std::fs::remove_dir_all(root).unwrap();
+	}
+
+	#[test]
+	fn widening_prefixes() {
+		let home = Some(Path::new("/home/user"));
+		assert!(widens_access(Path::new("/"), home));
+		assert!(widens_access(Path::new("/home"), home));
+		assert!(widens_access(Path::new("/home/user"), home));
+		assert!(widens_access(Path::new("/home/user/.ssh"), home));
+		assert!(widens_access(
+			Path::new("/home/user/.local/share/app"),
+			home
+		));
+		assert!(!widens_access(Path::new("/home/user/src/app/res"), home));
+		assert!(!widens_access(Path::new("/home/username"), home));
+		assert!(!widens_access(Path::new("/usr/share/app"), home));
+		assert!(!widens_access(Path::new("/usr/share/app"), None));
+	}
+}
@@ -1,6 +1,13 @@
 use crate::{
-	core::{Id, client::Client, error::Result},
-	nodes::{Message, Node, alias::get_original},
+	core::{
+		Id, MethodResponseSender, client::Client, error::Result, metrics::MessageKind,
+		quota::QuotaKind, recording::RecordedMessageKind,
+	},
+	nodes::{
+		AspectIdentifier, Message, Node,
+		alias::{Alias, get_original},
+		root::Root,
+	},
 };
 use dashmap::DashMap;
 use stardust_xr_wire::{
@@ -10,6 +17,7 @@ use stardust_xr_wire::{
 use std::{
 	os::fd::OwnedFd,
 	sync::{Arc, OnceLock, Weak},
+	time::Instant,
 };
 use tracing::{debug, debug_span};
 
@@ -23,10 +31,13 @@ impl Scenegraph {
 		self.client.get()?.upgrade()
 	}
 
-	pub fn add_node(&self, node: Node) -> Arc<Node> {
+	pub fn add_node(&self, node: Node) -> Result<Arc<Node>> {
+		if let Some(client) = self.get_client() {
+			client.check_quota(QuotaKind::Nodes, self.nodes.len() as u64 + 1)?;
+		}
 		let node_arc = Arc::new(node);
 		self.add_node_raw(node_arc.clone());
-		node_arc
+		Ok(node_arc)
 	}
 	pub fn add_node_raw(&self, node: Arc<Node>) {
 		debug!(node = ?&*node, "Add node");
@@ -38,9 +49,27 @@ impl Scenegraph {
 		get_original(node, true)
 	}
 
+	/// Get a node without resolving aliases to their original
+	pub fn get_node_raw(&self, node: Id) -> Option<Arc<Node>> {
+		self.nodes.get(&node).as_deref().cloned()
+	}
+	pub fn nodes(&self) -> Vec<Arc<Node>> {
+		self.nodes.iter().map(|node| node.value().clone()).collect()
+	}
+
 	pub fn remove_node(&self, node: Id) -> Option<Arc<Node>> {
 		debug!(node = node.0, "Remove node");
-		self.nodes.remove(&node).map(|(_, node)| node)
+		let (_, node) = self.nodes.remove(&node)?;
+		if node.get_aspect::<Alias>().is_ok()
+			&& let Some(client) = self.get_client()
+		{
+			client.quota.alias_removed();
+		}
+		Some(node)
+	}
+
+	pub fn node_count(&self) -> usize {
+		self.nodes.len()
 	}
 }
 impl scenegraph::Scenegraph for Scenegraph {
@@ -55,19 +84,53 @@ impl scenegraph::Scenegraph for Scenegraph {
 		let Some(client) = self.get_client() else {
 			return Err(ScenegraphError::NodeNotFound);
 		};
-		debug_span!("Handle signal", aspect_id, node_id, method).in_scope(|| {
+		client
+			.check_quota(QuotaKind::MessageRate, client.quota.count_message())
+			.map_err(|error| ScenegraphError::MemberError {
+				error: error.to_string(),
+			})?;
+		client.record(
+			RecordedMessageKind::Signal,
+			node_id,
+			aspect_id,
+			method,
+			data,
+			fds.len(),
+		);
+		client
+			.metrics
+			.message(MessageKind::ReceivedSignal, aspect_id, method, data.len());
+		let message = Message {
+			data: data.to_vec(),
+			fds,
+		};
+		// the root's signals begin and commit transactions, so they can't be part of one
+		let message = if aspect_id == Root::ID {
+			Some(message)
+		} else {
+			client
+				.transaction
+				.queue(&client, Id(node_id), aspect_id, method, message)
+				.map_err(|error| ScenegraphError::MemberError {
+					error: error.to_string(),
+				})?
+		};
+		let Some(message) = message else {
+			return Ok(());
+		};
+		let start = Instant::now();
+		let result = debug_span!("Handle signal", aspect_id, node_id, method).in_scope(|| {
 			self.get_node(Id(node_id))
 				.ok_or(ScenegraphError::NodeNotFound)?
-				.send_local_signal(
-					client,
-					aspect_id,
-					method,
-					Message {
-						data: data.to_vec(),
-						fds,
-					},
-				)
-		})
+				.send_local_signal(client.clone(), aspect_id, method, message)
+		});
+		client.metrics.handled(
+			MessageKind::ReceivedSignal,
+			aspect_id,
+			method,
+			start.elapsed(),
+		);
+		result
 	}
 	fn execute_method(
 		&self,
@@ -82,20 +145,58 @@ impl scenegraph::Scenegraph for Scenegraph {
 			response.send(Err(ScenegraphError::NodeNotFound));
 			return;
 		};
+		if let Err(error) = client.check_quota(QuotaKind::MessageRate, client.quota.count_message())
+		{
+			response.send(Err(ScenegraphError::MemberError {
+				error: error.to_string(),
+			}));
+			return;
+		}
+		client.record(
+			RecordedMessageKind::Method,
+			node_id,
+			aspect_id,
+			method,
+			data,
+			fds.len(),
+		);
+		client
+			.metrics
+			.message(MessageKind::ReceivedMethod, aspect_id, method, data.len());
 		debug!(aspect_id, node_id, method, "Handle method");
 		let Some(node) = self.get_node(Id(node_id)) else {
 			response.send(Err(ScenegraphError::NodeNotFound));
 			return;
 		};
+		let start = Instant::now();
+		let response = MethodResponseSender::from(response).on_sent({
+			let client = Arc::downgrade(&client);
+			move || {
+				if let Some(client) = client.upgrade() {
+					client.metrics.responded(
+						MessageKind::ReceivedMethod,
+						aspect_id,
+						method,
+						start.elapsed(),
+					);
+				}
+			}
+		});
 		node.execute_local_method(
-			client,
+			client.clone(),
 			aspect_id,
 			method,
 			Message {
 				data: data.to_vec(),
 				fds,
 			},
-			response.into(),
+			response,
+		);
+		client.metrics.handled(
+			MessageKind::ReceivedMethod,
+			aspect_id,
+			method,
+			start.elapsed(),
 		);
 	}
 }
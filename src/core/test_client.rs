@@ -0,0 +1,178 @@
+//! A client that talks to the server over a real socket pair from inside the test process,
+//! so tests can drive the protocol and then look at the scenegraph directly.
+
+use super::{Id, client::Client};
+use crate::nodes::{
+	AspectIdentifier,
+	root::{ClientState, ROOT_GET_STATE_SERVER_OPCODE, Root},
+};
+use parking_lot::Mutex;
+use serde::{Serialize, de::DeserializeOwned};
+use stardust_xr_wire::{
+	flex::{deserialize, serialize},
+	messenger::{self, MessageSenderHandle, MethodResponse},
+	scenegraph::{Scenegraph, ScenegraphError},
+};
+use std::{
+	os::fd::OwnedFd,
+	sync::{
+		Arc,
+		atomic::{AtomicU64, Ordering},
+	},
+	time::Duration,
+};
+use tokio::{net::UnixStream, sync::Notify, task::JoinHandle};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A signal the server sent to the test client
+#[derive(Debug, Clone)]
+pub struct ReceivedSignal {
+	pub node: u64,
+	pub aspect: u64,
+	pub method: u64,
+	pub data: Vec<u8>,
+}
+impl ReceivedSignal {
+	pub fn args<D: DeserializeOwned>(&self) -> D {
+		deserialize(&self.data, Vec::new()).unwrap()
+	}
+}
+
+#[derive(Default)]
+struct ReceivedSignals {
+	signals: Mutex<Vec<ReceivedSignal>>,
+	notify: Notify,
+}
+impl Scenegraph for ReceivedSignals {
+	fn send_signal(
+		&self,
+		node_id: u64,
+		aspect_id: u64,
+		method: u64,
+		data: &[u8],
+		_fds: Vec<OwnedFd>,
+	) -> Result<(), ScenegraphError> {
+		self.signals.lock().push(ReceivedSignal {
+			node: node_id,
+			aspect: aspect_id,
+			method,
+			data: data.to_vec(),
+		});
+		self.notify.notify_waiters();
+		Ok(())
+	}
+	fn execute_method(
+		&self,
+		_node_id: u64,
+		_aspect_id: u64,
+		_method: u64,
+		_data: &[u8],
+		_fds: Vec<OwnedFd>,
+		response: MethodResponse,
+	) {
+		response.send(Err(ScenegraphError::MemberNotFound));
+	}
+}
+
+pub struct TestClient {
+	/// The server side of the connection
+	pub client: Arc<Client>,
+	handle: MessageSenderHandle,
+	received: Arc<ReceivedSignals>,
+	tasks: [JoinHandle<()>; 2],
+	id_counter: AtomicU64,
+}
+impl TestClient {
+	pub async fn connect() -> Self {
+		let (server_end, client_end) = UnixStream::pair().unwrap();
+		let client = Client::from_connection(server_end).unwrap();
+
+		let (mut messenger_tx, mut messenger_rx) = messenger::create(client_end);
+		let handle = messenger_tx.handle();
+		let received = Arc::new(ReceivedSignals::default());
+		let flush = tokio::spawn(async move { while messenger_tx.flush().await.is_ok() {} });
+		let dispatch = tokio::spawn({
+			let received = received.clone();
+			async move { while messenger_rx.dispatch(&*received).await.is_ok() {} }
+		});
+
+		TestClient {
+			client,
+			handle,
+			received,
+			tasks: [flush, dispatch],
+			// keep clear of the IDs the server generates for aliases
+			id_counter: AtomicU64::new(1 << 32),
+		}
+	}
+
+	/// An ID for a node this client is about to create
+	pub fn new_id(&self) -> Id {
+		Id(self.id_counter.fetch_add(1, Ordering::Relaxed))
+	}
+
+	pub fn signal(&self, node: Id, aspect: u64, method: u64, args: impl Serialize) {
+		let (data, fds) = serialize(args).unwrap();
+		self.handle
+			.signal(node.0, aspect, method, &data, fds)
+			.unwrap();
+	}
+
+	/// Call a method, returning the server's error message if it failed
+	pub async fn method<D: DeserializeOwned>(
+		&self,
+		node: Id,
+		aspect: u64,
+		method: u64,
+		args: impl Serialize,
+	) -> Result<D, String> {
+		let (data, fds) = serialize(args).unwrap();
+		let response = tokio::time::timeout(
+			TIMEOUT,
+			self.handle.method(node.0, aspect, method, &data, fds),
+		)
+		.await
+		.expect("method call timed out")
+		.unwrap()
+		.map_err(|e| format!("{e:?}"))?;
+		let (data, fds) = response.into_components();
+		Ok(deserialize(&data, fds).unwrap())
+	}
+
+	/// Wait until the server has handled every message sent before this
+	pub async fn sync(&self) {
+		self.method::<ClientState>(Id(0), Root::ID, ROOT_GET_STATE_SERVER_OPCODE, ())
+			.await
+			.unwrap();
+	}
+
+	/// Wait for the next signal the server sends matching `filter`, including ones already received
+	pub async fn wait_for_signal(
+		&self,
+		mut filter: impl FnMut(&ReceivedSignal) -> bool,
+	) -> ReceivedSignal {
+		tokio::time::timeout(TIMEOUT, async {
+			loop {
+				let notified = self.received.notify.notified();
+				{
+					let mut signals = self.received.signals.lock();
+					if let Some(index) = signals.iter().position(&mut filter) {
+						return signals.remove(index);
+					}
+				}
+				notified.await;
+			}
+		})
+		.await
+		.expect("timed out waiting for a signal")
+	}
+}
+impl Drop for TestClient {
+	fn drop(&mut self) {
+		for task in &self.tasks {
+			task.abort();
+		}
+		self.client.disconnect(Ok(()));
+	}
+}
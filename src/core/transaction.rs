@@ -0,0 +1,222 @@
+use super::{
+	Id,
+	client::{CLIENTS, Client},
+	error::Result,
+	quota::QuotaKind,
+};
+use crate::nodes::Message;
+use bevy::{prelude::*, render::view::VisibilitySystems};
+use parking_lot::Mutex;
+use stardust_xr_server_foundation::bail;
+use stardust_xr_wire::scenegraph::ScenegraphError;
+use tracing::{debug_span, warn};
+
+pub struct TransactionPlugin;
+impl Plugin for TransactionPlugin {
+	fn build(&self, app: &mut App) {
+		// after everything that reads node state this frame, so a whole transaction shows up
+		// together on the next one
+		app.add_systems(
+			PostUpdate,
+			apply_committed
+				.after(TransformSystem::TransformPropagate)
+				.after(VisibilitySystems::CheckVisibility),
+		);
+	}
+}
+
+/// Run the signals of every transaction committed since the last frame
+pub fn apply_committed() {
+	for client in CLIENTS.get_vec() {
+		for signal in client.transaction.take_committed() {
+			let _span = debug_span!(
+				"Handle transaction signal",
+				aspect_id = signal.aspect,
+				node_id = signal.node.0,
+				method = signal.method
+			)
+			.entered();
+			let result = client
+				.scenegraph
+				.get_node(signal.node)
+				.ok_or(ScenegraphError::NodeNotFound)
+				.and_then(|node| {
+					node.send_local_signal(
+						client.clone(),
+						signal.aspect,
+						signal.method,
+						signal.message,
+					)
+				});
+			if let Err(e) = result {
+				warn!(
+					?client,
+					node = signal.node.0,
+					"Signal in transaction failed: {e}"
+				);
+			}
+		}
+	}
+}
+
+struct QueuedSignal {
+	node: Id,
+	aspect: u64,
+	method: u64,
+	message: Message,
+}
+
+/// Signals a client sent between `begin_transaction` and `commit_transaction`, held back so
+/// they all take effect in the same frame.
+#[derive(Default)]
+pub struct Transaction {
+	/// `None` while there's no open transaction
+	open: Mutex<Option<Vec<QueuedSignal>>>,
+	committed: Mutex<Vec<QueuedSignal>>,
+}
+impl Transaction {
+	pub fn begin(&self) -> Result<()> {
+		let mut open = self.open.lock();
+		if open.is_some() {
+			bail!("A transaction is already open");
+		}
+		open.replace(Vec::new());
+		Ok(())
+	}
+	pub fn commit(&self) -> Result<()> {
+		let Some(signals) = self.open.lock().take() else {
+			bail!("There is no open transaction to commit");
+		};
+		self.committed.lock().extend(signals);
+		Ok(())
+	}
+
+	/// Hold on to a signal if a transaction is open or committed signals are still waiting for
+	/// the next frame, otherwise give it back to be handled now. Fails if the client would go
+	/// over its quota of held back signals.
+	pub fn queue(
+		&self,
+		client: &Client,
+		node: Id,
+		aspect: u64,
+		method: u64,
+		message: Message,
+	) -> Result<Option<Message>> {
+		let mut open = self.open.lock();
+		let mut committed = self.committed.lock();
+		if open.is_none() && committed.is_empty() {
+			return Ok(Some(message));
+		}
+		let held = open.as_ref().map_or(0, Vec::len) + committed.len();
+		client.check_quota(QuotaKind::TransactionSignals, held as u64 + 1)?;
+		// signals sent after a commit wait behind it, or they'd run before the ones sent earlier
+		let signals = match open.as_mut() {
+			Some(signals) => signals,
+			None => &mut *committed,
+		};
+		signals.push(QueuedSignal {
+			node,
+			aspect,
+			method,
+			message,
+		});
+		Ok(None)
+	}
+
+	fn take_committed(&self) -> Vec<QueuedSignal> {
+		std::mem::take(&mut *self.committed.lock())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::core::test_client::TestClient;
+	use crate::nodes::{
+		AspectIdentifier,
+		root::{ROOT_BEGIN_TRANSACTION_SERVER_OPCODE, ROOT_COMMIT_TRANSACTION_SERVER_OPCODE, Root},
+		spatial::{SPATIAL_SET_LOCAL_TRANSFORM_SERVER_OPCODE, Spatial, Transform},
+	};
+	use glam::Vec3;
+
+	#[tokio::test]
+	async fn transaction_applied_on_frame() {
+		let client = TestClient::connect().await;
+		let root = client
+			.client
+			.scenegraph
+			.get_node(Id(0))
+			.unwrap()
+			.get_aspect::<Spatial>()
+			.unwrap();
+		let moved = Transform {
+			translation: Some([0.0, 1.0, 0.0].into()),
+			rotation: None,
+			scale: None,
+		};
+
+		client.signal(Id(0), Root::ID, ROOT_BEGIN_TRANSACTION_SERVER_OPCODE, ());
+		client.signal(
+			Id(0),
+			Spatial::ID,
+			SPATIAL_SET_LOCAL_TRANSFORM_SERVER_OPCODE,
+			moved,
+		);
+		client.sync().await;
+		assert_eq!(root.local_transform().w_axis.truncate(), Vec3::ZERO);
+
+		client.signal(Id(0), Root::ID, ROOT_COMMIT_TRANSACTION_SERVER_OPCODE, ());
+		client.sync().await;
+		assert_eq!(root.local_transform().w_axis.truncate(), Vec3::ZERO);
+
+		apply_committed();
+		assert_eq!(root.local_transform().w_axis.truncate(), Vec3::Y);
+	}
+
+	#[tokio::test]
+	async fn signals_after_commit_keep_order() {
+		let client = TestClient::connect().await;
+		let root = client
+			.client
+			.scenegraph
+			.get_node(Id(0))
+			.unwrap()
+			.get_aspect::<Spatial>()
+			.unwrap();
+		let move_to = |y: f32| Transform {
+			translation: Some([0.0, y, 0.0].into()),
+			rotation: None,
+			scale: None,
+		};
+
+		client.signal(Id(0), Root::ID, ROOT_BEGIN_TRANSACTION_SERVER_OPCODE, ());
+		client.signal(
+			Id(0),
+			Spatial::ID,
+			SPATIAL_SET_LOCAL_TRANSFORM_SERVER_OPCODE,
+			move_to(1.0),
+		);
+		client.signal(Id(0), Root::ID, ROOT_COMMIT_TRANSACTION_SERVER_OPCODE, ());
+		client.signal(
+			Id(0),
+			Spatial::ID,
+			SPATIAL_SET_LOCAL_TRANSFORM_SERVER_OPCODE,
+			move_to(2.0),
+		);
+		client.sync().await;
+		assert_eq!(root.local_transform().w_axis.truncate(), Vec3::ZERO);
+
+		apply_committed();
+		assert_eq!(root.local_transform().w_axis.truncate(), Vec3::Y * 2.0);
+
+		// with nothing held back signals are handled right away again
+		client.signal(
+			Id(0),
+			Spatial::ID,
+			SPATIAL_SET_LOCAL_TRANSFORM_SERVER_OPCODE,
+			move_to(3.0),
+		);
+		client.sync().await;
+		assert_eq!(root.local_transform().w_axis.truncate(), Vec3::Y * 3.0);
+	}
+}
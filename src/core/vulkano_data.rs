@@ -1,4 +1,7 @@
-use std::sync::{Arc, OnceLock};
+use std::sync::{
+	Arc, OnceLock,
+	atomic::{AtomicBool, Ordering},
+};
 
 use bevy::{
 	app::Plugin,
@@ -20,9 +23,24 @@ use vulkano::{
 };
 use wgpu_hal::vulkan::Api as VulkanHal;
 
-use crate::vk_device_exts;
+use crate::{core::error::Result, vk_device_exts};
+use stardust_xr_server_foundation::ensure;
 
 pub static VULKANO_CONTEXT: OnceLock<VulkanoContext> = OnceLock::new();
+static HEADLESS: AtomicBool = AtomicBool::new(false);
+
+/// `VULKANO_CONTEXT` never gets set without a GPU, so make `vulkano_context` fail instead of waiting
+pub fn set_headless() {
+	HEADLESS.store(true, Ordering::Relaxed);
+}
+/// Wait for the vulkano context to be set up, or fail right away when running headless
+pub fn vulkano_context() -> Result<&'static VulkanoContext> {
+	ensure!(
+		!HEADLESS.load(Ordering::Relaxed),
+		"The server is running headless without a GPU"
+	);
+	Ok(VULKANO_CONTEXT.wait())
+}
 
 #[expect(dead_code)]
 pub struct VulkanoContext {
@@ -0,0 +1,160 @@
+use super::client::{CLIENTS, Client};
+use crate::{config::ServerConfig, objects::object_path};
+use color_eyre::eyre::eyre;
+use serde::Deserialize;
+use std::{
+	sync::Arc,
+	time::{Duration, Instant},
+};
+use tracing::{info, warn};
+use zbus::{Connection, interface, object_server::SignalEmitter};
+
+/// The `[watchdog]` section of `server.toml`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WatchdogConfig {
+	/// How often every client is checked, in milliseconds
+	pub check_interval_ms: u64,
+	/// Disconnect clients that have been hung for this many seconds. Clients that are just idle
+	/// also stop sending messages, so this is off by default.
+	pub disconnect_after_secs: Option<f32>,
+}
+impl Default for WatchdogConfig {
+	fn default() -> Self {
+		WatchdogConfig {
+			check_interval_ms: 250,
+			disconnect_after_secs: None,
+		}
+	}
+}
+
+enum WatchdogEvent {
+	Hung(i32, String),
+	Recovered(i32),
+	Disconnected(i32, String),
+}
+
+/// Periodically check every client with `Client::unresponsive` and report the ones that hang
+/// over DBus so shells can show "not responding" dialogs.
+pub async fn watch_clients(connection: Connection) {
+	let config = ServerConfig::get().watchdog.clone();
+	if let Err(e) = connection
+		.object_server()
+		.at(object_path("Watchdog"), Watchdog)
+		.await
+	{
+		warn!("Couldn't add the watchdog to dbus: {e}");
+	}
+	let disconnect_after = config.disconnect_after_secs.and_then(|secs| {
+		Duration::try_from_secs_f32(secs)
+			.inspect_err(|e| {
+				warn!(
+					"Ignoring watchdog.disconnect_after_secs = {secs}, hung clients won't be disconnected: {e}"
+				)
+			})
+			.ok()
+	});
+	let mut interval =
+		tokio::time::interval(Duration::from_millis(config.check_interval_ms.max(1)));
+	loop {
+		interval.tick().await;
+		let events = CLIENTS
+			.get_vec()
+			.into_iter()
+			// the internal client has no connection to hang
+			.filter(|client| client.message_sender_handle.is_some())
+			.filter_map(|client| check_client(&client, disconnect_after))
+			.collect::<Vec<_>>();
+		if events.is_empty() {
+			continue;
+		}
+		let Ok(watchdog) = connection
+			.object_server()
+			.interface::<_, Watchdog>(object_path("Watchdog"))
+			.await
+		else {
+			continue;
+		};
+		let emitter = watchdog.signal_emitter();
+		for event in events {
+			let _ = match event {
+				WatchdogEvent::Hung(pid, exe) => Watchdog::client_hung(emitter, pid, exe).await,
+				WatchdogEvent::Recovered(pid) => Watchdog::client_recovered(emitter, pid).await,
+				WatchdogEvent::Disconnected(pid, reason) => {
+					Watchdog::client_disconnected(emitter, pid, reason).await
+				}
+			};
+		}
+		let _ = watchdog.get().await.hung_clients_changed(emitter).await;
+	}
+}
+
+fn check_client(client: &Arc<Client>, disconnect_after: Option<Duration>) -> Option<WatchdogEvent> {
+	let pid = client.pid.unwrap_or_default();
+	let mut hung_since = client.hung_since.lock();
+	match (*hung_since, client.unresponsive()) {
+		(None, true) => {
+			let exe = client
+				.get_cmdline()
+				.and_then(|cmdline| cmdline.into_iter().next())
+				.unwrap_or_default();
+			warn!(pid, exe, "Client is not responding");
+			*hung_since = Some(Instant::now());
+			Some(WatchdogEvent::Hung(pid, exe))
+		}
+		(Some(_), false) => {
+			info!(pid, "Client is responding again");
+			*hung_since = None;
+			Some(WatchdogEvent::Recovered(pid))
+		}
+		(Some(since), true) => {
+			let grace_period = disconnect_after?;
+			if since.elapsed() < grace_period {
+				return None;
+			}
+			let reason = format!(
+				"Unresponsive for over {:.1} seconds",
+				grace_period.as_secs_f32()
+			);
+			drop(hung_since);
+			client.disconnect(Err(eyre!(reason.clone())));
+			Some(WatchdogEvent::Disconnected(pid, reason))
+		}
+		(None, false) => None,
+	}
+}
+
+struct Watchdog;
+#[interface(name = "org.stardustxr.Watchdog")]
+impl Watchdog {
+	/// PIDs of all clients that currently aren't responding
+	#[zbus(property)]
+	fn hung_clients(&self) -> Vec<i32> {
+		CLIENTS
+			.get_vec()
+			.iter()
+			.filter(|client| client.hung_since.lock().is_some())
+			.filter_map(|client| client.pid)
+			.collect()
+	}
+
+	/// Disconnect a client, e.g. when the user picks "force quit" in a not responding dialog
+	fn disconnect_client(&self, pid: i32) {
+		for client in CLIENTS.get_vec() {
+			if client.pid == Some(pid) && client.message_sender_handle.is_some() {
+				client.disconnect(Err(eyre!("Disconnected through the watchdog")));
+			}
+		}
+	}
+
+	#[zbus(signal)]
+	async fn client_hung(emitter: &SignalEmitter<'_>, pid: i32, exe: String) -> zbus::Result<()>;
+	#[zbus(signal)]
+	async fn client_recovered(emitter: &SignalEmitter<'_>, pid: i32) -> zbus::Result<()>;
+	#[zbus(signal)]
+	async fn client_disconnected(
+		emitter: &SignalEmitter<'_>,
+		pid: i32,
+		reason: String,
+	) -> zbus::Result<()>;
+}
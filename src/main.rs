@@ -3,6 +3,7 @@
 #![allow(clippy::too_many_arguments)]
 #![allow(clippy::type_complexity)]
 mod bevy_int;
+mod config;
 mod core;
 mod nodes;
 mod objects;
@@ -57,10 +58,14 @@ use bevy_mod_xr::{
 	camera::XrProjection,
 	session::{XrFirst, XrHandleEvents, XrSessionPlugin},
 };
-use clap::Parser;
+use clap::{Parser, Subcommand};
+use config::ServerConfig;
 use core::{
-	client::{Client, tick_internal_client},
+	client::{Client, RECORDING_DIR, shutdown_clients, tick_internal_client},
+	metrics::serve_metrics,
 	task,
+	transaction::TransactionPlugin,
+	watchdog::watch_clients,
 };
 use directories::ProjectDirs;
 use nodes::{
@@ -69,10 +74,12 @@ use nodes::{
 		lines::LinesNodePlugin, model::ModelNodePlugin, sky::SkyPlugin, text::TextNodePlugin,
 	},
 	fields::FieldDebugGizmoPlugin,
+	inspect::ScenegraphInspectorPlugin,
 	spatial::SpatialNodePlugin,
 };
 use objects::{
-	hmd::HmdPlugin,
+	claim_dbus_names,
+	hmd::{HeadlessHmdPlugin, HmdPlugin},
 	input::{
 		mouse_pointer::FlatscreenInputPlugin, oxr_controller::ControllerPlugin,
 		oxr_hand::HandPlugin,
@@ -80,7 +87,7 @@ use objects::{
 	play_space::PlaySpacePlugin,
 };
 use openxr::{EnvironmentBlendMode, ReferenceSpaceType};
-use session::{launch_start, save_session};
+use session::{SessionConfig, SessionsCommand, autosave, launch_start, save_session};
 use stardust_xr_gluon::object_registry::ObjectRegistry;
 use stardust_xr_wire::server::LockedSocket;
 use std::{
@@ -88,70 +95,182 @@ use std::{
 	path::PathBuf,
 	str::FromStr,
 	sync::{Arc, OnceLock},
+	time::Duration,
 };
 use tokio::{net::UnixListener, sync::Notify, task::JoinError};
-use tracing::{error, info, metadata::LevelFilter};
+use tracing::{error, info, metadata::LevelFilter, warn};
 use tracing_subscriber::{EnvFilter, filter::Directive, fmt, prelude::*};
 #[cfg(feature = "wayland")]
 use wayland::{Wayland, WaylandPlugin};
 use zbus::{Connection, fdo::ObjectManager};
 
 use crate::{
-	core::vulkano_data::VulkanoPlugin,
+	core::vulkano_data::{VulkanoPlugin, set_headless},
 	nodes::{camera::CameraNodePlugin, drawable::dmatex::DmatexPlugin},
 };
 
 #[derive(Debug, Clone, Parser)]
 #[clap(author, version, about, long_about = None)]
 struct CliArgs {
+	#[clap(subcommand)]
+	command: Option<CliCommand>,
+
 	/// Force flatscreen mode and use the mouse pointer as a 3D pointer
-	#[clap(short, long, action)]
+	#[clap(short, long, action, overrides_with = "no_force_flatscreen")]
 	force_flatscreen: bool,
+	#[clap(long, action, overrides_with = "force_flatscreen", hide = true)]
+	no_force_flatscreen: bool,
 
 	/// Force disable the flatscreen window
-	#[clap(short, long, action)]
+	#[clap(short, long, action, overrides_with = "no_xr_only")]
 	xr_only: bool,
+	#[clap(long, action, overrides_with = "xr_only", hide = true)]
+	no_xr_only: bool,
+
+	/// Run without OpenXR, a window or a GPU, e.g. to test clients in CI. Nothing gets rendered and importing dmatexes fails.
+	#[clap(long, action, overrides_with = "no_headless")]
+	headless: bool,
+	#[clap(long, action, overrides_with = "headless", hide = true)]
+	no_headless: bool,
 
 	/// Replaces the flatscreen mode with a first person spectator camera
-	#[clap(short, long, action)]
+	#[clap(short, long, action, overrides_with = "no_spectator")]
 	spectator: bool,
+	#[clap(long, action, overrides_with = "spectator", hide = true)]
+	no_spectator: bool,
 
 	/// Creates a transparent window fot the flatscreen mode
-	#[clap(short, long, action)]
+	#[clap(short, long, action, overrides_with = "no_transparent_flatscreen")]
 	transparent_flatscreen: bool,
+	#[clap(long, action, overrides_with = "transparent_flatscreen", hide = true)]
+	no_transparent_flatscreen: bool,
 
 	/// If monado insists on emulating them, set this flag...we want the raw input
-	#[clap(long)]
+	#[clap(long, overrides_with = "no_disable_controllers")]
 	disable_controllers: bool,
+	#[clap(long, action, overrides_with = "disable_controllers", hide = true)]
+	no_disable_controllers: bool,
 	/// If monado insists on emulating , set this flag...we want the raw input
-	#[clap(long)]
+	#[clap(long, overrides_with = "no_disable_hands")]
 	disable_hands: bool,
+	#[clap(long, action, overrides_with = "disable_hands", hide = true)]
+	no_disable_hands: bool,
 
 	/// Make hands fully transparent for passthrough (useful for wivrn)
-	#[clap(long, action)]
+	#[clap(long, action, overrides_with = "no_transparent_hands")]
 	transparent_hands: bool,
+	#[clap(long, action, overrides_with = "transparent_hands", hide = true)]
+	no_transparent_hands: bool,
 
 	/// Disable pipelined rendering, in case of weird behavior, will decrease performance
-	#[clap(long, action)]
+	#[clap(long, action, overrides_with = "no_disable_pipelined_rendering")]
 	disable_pipelined_rendering: bool,
+	#[clap(
+		long,
+		action,
+		overrides_with = "disable_pipelined_rendering",
+		hide = true
+	)]
+	no_disable_pipelined_rendering: bool,
 
 	/// Run Stardust XR as an overlay with given priority
 	#[clap(id = "PRIORITY", short = 'o', long = "overlay", action)]
 	overlay_priority: Option<u32>,
 
 	/// Debug the clients started by the server
-	#[clap(short = 'd', long = "debug", action)]
+	#[clap(
+		short = 'd',
+		long = "debug",
+		action,
+		overrides_with = "no_debug_launched_clients"
+	)]
 	debug_launched_clients: bool,
-
-	/// Run a script when ready for clients to connect. If this is not set the script at $HOME/.config/stardust/startup will be ran if it exists.
+	#[clap(
+		long = "no-debug",
+		action,
+		overrides_with = "debug_launched_clients",
+		hide = true
+	)]
+	no_debug_launched_clients: bool,
+
+	/// Run a script when ready for clients to connect. If this is not set the clients in $HOME/.config/stardust/startup.toml will be launched, or the script at $HOME/.config/stardust/startup will be ran if it exists.
 	#[clap(id = "PATH", short = 'e', long = "execute-startup-script", action)]
 	startup_script: Option<PathBuf>,
 
-	/// Restore the session with the given ID (or `latest`), ignoring the startup script. Sessions are stored in directories at `~/.local/state/stardust/`.
+	/// Record the messages of every client to a file in this directory, to play back with `stardust-xr-replay`
+	#[clap(id = "DIR", long = "record", action)]
+	record: Option<PathBuf>,
+
+	/// Restore the session with the given ID (or `latest`, or `recovery` for the last autosave), ignoring the startup script. Sessions are stored in directories at `~/.local/state/stardust/`.
 	#[clap(id = "SESSION_ID", long = "restore", action)]
 	restore: Option<String>,
 }
 
+impl CliArgs {
+	/// Fill in the flags that weren't passed from `server.toml`. Every flag has a hidden
+	/// `--no-` version to turn it off when `server.toml` turns it on.
+	fn apply_config(&mut self, config: &ServerConfig) {
+		let display = &config.display;
+		merge_flag(
+			&mut self.force_flatscreen,
+			self.no_force_flatscreen,
+			display.force_flatscreen,
+		);
+		merge_flag(&mut self.xr_only, self.no_xr_only, display.xr_only);
+		merge_flag(&mut self.headless, self.no_headless, display.headless);
+		merge_flag(&mut self.spectator, self.no_spectator, display.spectator);
+		merge_flag(
+			&mut self.transparent_flatscreen,
+			self.no_transparent_flatscreen,
+			display.transparent_flatscreen,
+		);
+		self.overlay_priority = self.overlay_priority.or(display.overlay_priority);
+		merge_flag(
+			&mut self.disable_pipelined_rendering,
+			self.no_disable_pipelined_rendering,
+			display.disable_pipelined_rendering,
+		);
+
+		let input = &config.input;
+		merge_flag(
+			&mut self.disable_controllers,
+			self.no_disable_controllers,
+			input.disable_controllers,
+		);
+		merge_flag(
+			&mut self.disable_hands,
+			self.no_disable_hands,
+			input.disable_hands,
+		);
+		merge_flag(
+			&mut self.transparent_hands,
+			self.no_transparent_hands,
+			input.transparent_hands,
+		);
+
+		let session = &config.session;
+		merge_flag(
+			&mut self.debug_launched_clients,
+			self.no_debug_launched_clients,
+			session.debug_launched_clients,
+		);
+		if self.startup_script.is_none() {
+			self.startup_script = session.startup_script.clone();
+		}
+	}
+}
+/// The config value only counts if neither the flag nor its `--no-` version was passed
+fn merge_flag(flag: &mut bool, no_flag: bool, config: bool) {
+	*flag |= !no_flag && config;
+}
+
+#[derive(Debug, Clone, Subcommand)]
+enum CliCommand {
+	/// Manage saved sessions instead of running the server
+	#[clap(subcommand)]
+	Sessions(SessionsCommand),
+}
+
 pub type BevyMaterial = StandardMaterial;
 
 static STARDUST_INSTANCE: OnceLock<String> = OnceLock::new();
@@ -184,7 +303,36 @@ async fn main() -> Result<AppExit, JoinError> {
 		);
 	registry.with(log_layer).init();
 
-	let cli_args = CliArgs::parse();
+	let mut cli_args = CliArgs::parse();
+
+	let project_dirs = ProjectDirs::from("", "", "stardust");
+	if project_dirs.is_none() {
+		error!(
+			"Unable to get Stardust project directories, default skybox and startup script will not work."
+		);
+	}
+
+	cli_args.apply_config(ServerConfig::load(project_dirs.as_ref()));
+
+	if let Some(CliCommand::Sessions(command)) = cli_args.command.clone() {
+		if let Err(e) = command.run(project_dirs.as_ref()) {
+			eprintln!("{e}");
+			return Ok(AppExit::error());
+		}
+		return Ok(AppExit::Success);
+	}
+
+	if let Some(recording_dir) = &cli_args.record {
+		if let Err(e) = std::fs::create_dir_all(recording_dir) {
+			error!(recording_dir = ?recording_dir.display(), "Couldn't create the recording directory: {e}");
+		} else {
+			let _ = RECORDING_DIR.set(recording_dir.clone());
+		}
+	}
+
+	if cli_args.headless {
+		set_headless();
+	}
 
 	let locked_socket =
 		LockedSocket::get_free().expect("Unable to find a free stardust socket path");
@@ -208,24 +356,10 @@ async fn main() -> Result<AppExit, JoinError> {
 	.unwrap();
 	info!("Init client join loop");
 
-	let project_dirs = ProjectDirs::from("", "", "stardust");
-	if project_dirs.is_none() {
-		error!(
-			"Unable to get Stardust project directories, default skybox and startup script will not work."
-		);
-	}
-
 	let dbus_connection = Connection::session()
 		.await
 		.expect("Could not open dbus session");
-	// why is this requested here? should there be a specific server bus name that we check
-	// instead?
-	dbus_connection
-		.request_name("org.stardustxr.HMD")
-		.await
-		.expect(
-			"Another instance of the server is running. This is not supported currently (but is planned).",
-		);
+	claim_dbus_names(&dbus_connection).await;
 
 	dbus_connection
 		.object_server()
@@ -233,10 +367,13 @@ async fn main() -> Result<AppExit, JoinError> {
 		.await
 		.expect("Couldn't add the object manager");
 
+	task::new(|| "Client watchdog", watch_clients(dbus_connection.clone())).unwrap();
+	task::new(|| "Client metrics", serve_metrics(dbus_connection.clone())).unwrap();
+
 	let object_registry = ObjectRegistry::new(&dbus_connection).await;
 
 	#[cfg(feature = "wayland")]
-	let _wayland = Wayland::new().expect("Couldn't create Wayland instance");
+	let wayland = Wayland::new().expect("Couldn't create Wayland instance");
 
 	let ready_notifier = Arc::new(Notify::new());
 	let io_loop = tokio::task::spawn_blocking({
@@ -245,28 +382,45 @@ async fn main() -> Result<AppExit, JoinError> {
 		let cli_args = cli_args.clone();
 		let dbus_connection = dbus_connection.clone();
 		move || {
-			bevy_loop(
-				ready_notifier,
-				project_dirs,
-				cli_args,
-				dbus_connection,
-				object_registry,
-			)
+			if cli_args.headless {
+				headless_bevy_loop(ready_notifier, dbus_connection, object_registry)
+			} else {
+				bevy_loop(
+					ready_notifier,
+					project_dirs,
+					cli_args,
+					dbus_connection,
+					object_registry,
+				)
+			}
 		}
 	});
 	ready_notifier.notified().await;
-	let mut startup_children = project_dirs
+	let startup_clients = project_dirs
 		.as_ref()
 		.map(|project_dirs| launch_start(&cli_args, project_dirs))
 		.unwrap_or_default();
-	let return_value = io_loop.await;
+	// autosaving stops as soon as the bevy loop exits since it's only polled here
+	let return_value = tokio::select! {
+		return_value = io_loop => return_value,
+		_ = autosave(project_dirs.as_ref()) => unreachable!(),
+	};
 	info!("Stopping...");
+	let deadline_secs = ServerConfig::get().session.shutdown_deadline_secs;
+	let deadline = Duration::try_from_secs_f32(deadline_secs.max(0.0)).unwrap_or_else(|e| {
+		let default = SessionConfig::default().shutdown_deadline_secs;
+		warn!("Invalid session.shutdown_deadline_secs of {deadline_secs}, using {default}: {e}");
+		Duration::from_secs_f32(default)
+	});
+	// clients get to finish what they were doing before their state is saved
+	shutdown_clients(deadline).await;
 	if let Some(project_dirs) = project_dirs {
 		save_session(&project_dirs).await;
 	}
-	for mut startup_child in startup_children.drain(..) {
-		let _ = startup_child.kill();
-	}
+	startup_clients.kill();
+	#[cfg(feature = "wayland")]
+	drop(wayland);
+	let _ = dbus_connection.close().await;
 
 	info!("Cleanly shut down Stardust");
 	return_value
@@ -292,6 +446,59 @@ pub fn vk_device_exts() -> Vec<&'static std::ffi::CStr> {
 	}
 	exts
 }
+/// A task pool plugin whose threads can use tokio, along with the callback that makes that work
+fn tokio_task_pool_plugin() -> (TaskPoolPlugin, Arc<dyn Fn() + Send + Sync>) {
+	let mut task_pool_plugin = TaskPoolPlugin::default();
+	// make tokio work
+	let handle = tokio::runtime::Handle::current();
+	let enter_runtime_context: Arc<dyn Fn() + Send + Sync> = Arc::new(move || {
+		// TODO: this might be a memory leak
+		std::mem::forget(handle.enter());
+	});
+	task_pool_plugin.task_pool_options.io.on_thread_spawn = Some(enter_runtime_context.clone());
+	task_pool_plugin.task_pool_options.compute.on_thread_spawn =
+		Some(enter_runtime_context.clone());
+	task_pool_plugin
+		.task_pool_options
+		.async_compute
+		.on_thread_spawn = Some(enter_runtime_context.clone());
+	(task_pool_plugin, enter_runtime_context)
+}
+
+/// Run only the parts of the server that clients talk to, without OpenXR, a window or rendering
+fn headless_bevy_loop(
+	ready_notifier: Arc<Notify>,
+	dbus_connection: Connection,
+	object_registry: Arc<ObjectRegistry>,
+) -> AppExit {
+	let mut app = App::new();
+	app.insert_resource(DbusConnection(dbus_connection));
+	app.insert_resource(ObjectRegistryRes(object_registry));
+	let (task_pool_plugin, _) = tokio_task_pool_plugin();
+	app.add_plugins(
+		MinimalPlugins
+			.set(task_pool_plugin)
+			.set(ScheduleRunnerPlugin::run_loop(Duration::from_secs_f64(
+				1.0 / 90.0,
+			))),
+	);
+	app.add_plugins((TransformPlugin, TerminalCtrlCHandlerPlugin));
+	app.add_schedule(Schedule::new(PreFrameWait));
+	app.add_plugins((
+		EntityHandlePlugin,
+		TransactionPlugin,
+		SpatialNodePlugin,
+		PlaySpacePlugin,
+		HeadlessHmdPlugin,
+		ScenegraphInspectorPlugin,
+	));
+	app.add_systems(PostStartup, move || {
+		ready_notifier.notify_waiters();
+	});
+	app.add_systems(First, headless_step);
+	app.run()
+}
+
 fn bevy_loop(
 	ready_notifier: Arc<Notify>,
 	_project_dirs: Option<ProjectDirs>,
@@ -308,6 +515,8 @@ fn bevy_loop(
 	});
 	app.add_plugins(AssetPlugin {
 		meta_check: AssetMetaCheck::Never,
+		// every path a client asks for goes through `Client::get_resource_file` first, which
+		// applies the `[resources]` policy, and they're all outside of bevy's asset directory
 		unapproved_path_mode: UnapprovedPathMode::Allow,
 		..default()
 	});
@@ -347,20 +556,7 @@ fn bevy_loop(
 		.add(GizmoPlugin)
 		.add(WindowPlugin::default())
 		.add(DmabufImportPlugin);
-	let mut task_pool_plugin = TaskPoolPlugin::default();
-	// make tokio work
-	let handle = tokio::runtime::Handle::current();
-	let enter_runtime_context = Arc::new(move || {
-		// TODO: this might be a memory leak
-		std::mem::forget(handle.enter());
-	});
-	task_pool_plugin.task_pool_options.io.on_thread_spawn = Some(enter_runtime_context.clone());
-	task_pool_plugin.task_pool_options.compute.on_thread_spawn =
-		Some(enter_runtime_context.clone());
-	task_pool_plugin
-		.task_pool_options
-		.async_compute
-		.on_thread_spawn = Some(enter_runtime_context.clone());
+	let (task_pool_plugin, enter_runtime_context) = tokio_task_pool_plugin();
 	plugins = plugins.set(task_pool_plugin);
 	if (std::env::var("DISPLAY").is_ok_and(|s| !s.is_empty())
 		|| std::env::var("WAYLAND_DISPLAY").is_ok_and(|s| !s.is_empty()))
@@ -435,7 +631,9 @@ fn bevy_loop(
 
 	app.add_plugins(bevy_equirect::EquirectangularPlugin);
 	// app.add_plugins(HandGizmosPlugin);
-	app.world_mut().resource_mut::<AmbientLight>().brightness = 1000.0;
+	let display_config = &ServerConfig::get().display;
+	app.world_mut().resource_mut::<AmbientLight>().brightness =
+		display_config.ambient_light_brightness;
 	if let Some(priority) = args.overlay_priority {
 		app.insert_resource(OxrOverlaySettings {
 			session_layer_placement: priority,
@@ -443,11 +641,12 @@ fn bevy_loop(
 		});
 	}
 	app.insert_resource(OxrSessionConfig {
-		blend_mode_preference: vec![
-			EnvironmentBlendMode::ALPHA_BLEND,
-			EnvironmentBlendMode::ADDITIVE,
-			EnvironmentBlendMode::OPAQUE,
-		],
+		blend_mode_preference: display_config
+			.blend_modes
+			.iter()
+			.copied()
+			.map(EnvironmentBlendMode::from)
+			.collect(),
 		..default()
 	});
 	let mut pre_frame_wait = Schedule::new(PreFrameWait);
@@ -462,7 +661,12 @@ fn bevy_loop(
 	}
 	// the Stardust server plugins
 	// infra plugins
-	app.add_plugins((EntityHandlePlugin, DmatexPlugin, VulkanoPlugin));
+	app.add_plugins((
+		EntityHandlePlugin,
+		DmatexPlugin,
+		VulkanoPlugin,
+		TransactionPlugin,
+	));
 	// node plugins
 	app.add_plugins((
 		SpatialNodePlugin,
@@ -491,7 +695,11 @@ fn bevy_loop(
 	// feature plugins
 	#[cfg(feature = "wayland")]
 	app.add_plugins(WaylandPlugin);
-	app.add_plugins((TrackingOffsetPlugin, FieldDebugGizmoPlugin));
+	app.add_plugins((
+		TrackingOffsetPlugin,
+		FieldDebugGizmoPlugin,
+		ScenegraphInspectorPlugin,
+	));
 	app.add_systems(PostStartup, move || {
 		ready_notifier.notify_waiters();
 	});
@@ -559,6 +767,13 @@ fn xr_step(world: &mut World) {
 	tick_internal_client();
 }
 
+fn headless_step(world: &mut World) {
+	world.run_schedule(PreFrameWait);
+	let time = world.resource::<bevy::prelude::Time>().delta_secs_f64();
+	nodes::root::Root::send_frame_events(time);
+	tick_internal_client();
+}
+
 pub fn get_time(pipelined: bool, state: &OxrFrameState) -> openxr::Time {
 	if pipelined {
 		openxr::Time::from_nanos(
@@ -1,5 +1,7 @@
 use super::{Aspect, AspectIdentifier, Node};
-use crate::core::{Id, MethodResponseSender, client::Client, error::Result, registry::Registry};
+use crate::core::{
+	Id, MethodResponseSender, client::Client, error::Result, quota::QuotaKind, registry::Registry,
+};
 use std::{
 	ops::Add,
 	sync::{Arc, Weak},
@@ -34,6 +36,7 @@ impl Alias {
 		info: AliasInfo,
 		list: Option<&AliasList>,
 	) -> Result<Arc<Node>> {
+		client.check_quota(QuotaKind::Aliases, client.quota.alias_count() + 1)?;
 		let node = Node::generate(client, true).add_to_scenegraph()?;
 		Self::add_to(&node, original, info)?;
 		if let Some(list) = list {
@@ -48,6 +51,7 @@ impl Alias {
 		info: AliasInfo,
 		list: Option<&AliasList>,
 	) -> Result<Arc<Node>> {
+		client.check_quota(QuotaKind::Aliases, client.quota.alias_count() + 1)?;
 		let node = Node::from_id(client, new_id, true).add_to_scenegraph()?;
 		Self::add_to(&node, original, info)?;
 		if let Some(list) = list {
@@ -64,6 +68,9 @@ impl Alias {
 		};
 		let alias = original.aliases.add(alias);
 		new_node.add_aspect_raw(alias);
+		if let Some(client) = new_node.get_client() {
+			client.quota.alias_added();
+		}
 		Ok(())
 	}
 }
@@ -140,6 +147,9 @@ impl AliasList {
 	pub fn get_aliases(&self) -> Vec<Arc<Node>> {
 		self.0.get_valid_contents()
 	}
+	pub fn contains(&self, alias: &Node) -> bool {
+		self.0.contains(alias)
+	}
 	pub fn remove_aspect<A: AspectIdentifier>(&self, aspect: &A) {
 		self.0.retain(|node| {
 			let Some(original) = get_original(node.clone(), false) else {
@@ -5,7 +5,6 @@ use crate::core::Id;
 use crate::core::client::Client;
 use crate::core::error::Result;
 use crate::core::registry::Registry;
-use crate::core::resource::get_resource_file;
 use crate::nodes::spatial::{SPATIAL_ASPECT_ALIAS_INFO, Spatial, Transform};
 use bevy::audio::{PlaybackMode, Volume};
 use bevy_mod_openxr::session::OxrSession;
@@ -104,12 +103,9 @@ pub struct Sound {
 impl Sound {
 	pub fn add_to(node: &Arc<Node>, resource_id: ResourceID) -> Result<Arc<Sound>> {
 		let client = node.get_client().ok_or_else(|| eyre!("Client not found"))?;
-		let pending_audio_path = get_resource_file(
-			&resource_id,
-			client.base_resource_prefixes.lock().iter(),
-			&[OsStr::new("wav"), OsStr::new("mp3")],
-		)
-		.ok_or_else(|| eyre!("Resource not found"))?;
+		let pending_audio_path = client
+			.get_resource_file(&resource_id, &[OsStr::new("wav"), OsStr::new("mp3")])?
+			.ok_or_else(|| eyre!("Resource not found"))?;
 		let sound = Sound {
 			spatial: node.get_aspect::<Spatial>().unwrap().clone(),
 			volume: 1.0,
@@ -33,7 +33,7 @@ use vulkano::sync::semaphore::{
 
 use crate::{
 	bevy_int::bevy_channel::{BevyChannel, BevyChannelReader},
-	core::vulkano_data::VULKANO_CONTEXT,
+	core::vulkano_data::{VULKANO_CONTEXT, vulkano_context},
 	nodes::drawable::{DmatexSize, model::ModelNodeSystemSet},
 };
 
@@ -41,6 +41,7 @@ use crate::{
 pub struct ImportedDmatex {
 	tex: ImportedTexture,
 	sync_obj: TimelineSyncObj,
+	byte_size: u64,
 	bevy_image_handle: OnceLock<Handle<bevy::image::Image>>,
 	// TODO: handle destruction
 	bevy_custom_view: OnceLock<ManualTextureViewHandle>,
@@ -70,13 +71,14 @@ impl ImportedDmatex {
 		planes: Vec<super::DmatexPlane>,
 		timeline_syncobj_fd: OwnedFd,
 	) -> Result<Arc<Self>> {
+		let byte_size = Self::estimate_byte_size(&size, &planes);
 		let DmatexSize::Dim2D(res) = size else {
 			bail!("non 2d dmatex are not implemented yet");
 		};
 		if array_layers.is_some_and(|v| v != 1) {
 			bail!("array layers in dmatex is not implemented yet");
 		}
-		let vk = VULKANO_CONTEXT.wait();
+		let vk = vulkano_context()?;
 		let render_node = match DRM_RENDER_NODE.get() {
 			Some(v) => v,
 			None => {
@@ -123,12 +125,24 @@ impl ImportedDmatex {
 		let tex = Arc::new(Self {
 			tex,
 			sync_obj,
+			byte_size,
 			bevy_image_handle: OnceLock::new(),
 			bevy_custom_view: OnceLock::new(),
 		});
 		NEW_DMATEXES.send(tex.clone());
 		Ok(tex)
 	}
+	/// Size of a dmatex's memory in bytes before importing it, so quotas can be checked first
+	pub fn estimate_byte_size(size: &DmatexSize, planes: &[super::DmatexPlane]) -> u64 {
+		let DmatexSize::Dim2D(res) = size else {
+			return 0;
+		};
+		// rough estimate, planes with subsampling are counted at full height
+		planes
+			.iter()
+			.map(|p| p.row_size as u64 * res.y as u64)
+			.sum()
+	}
 	/// only use for readonly uses, write operations should sync with a vulkan semaphore
 	pub fn signal_on_drop(self: &Arc<Self>, point: u64) -> SignalOnDrop {
 		SignalOnDrop {
@@ -137,6 +151,10 @@ impl ImportedDmatex {
 			consumed: false,
 		}
 	}
+	/// Approximate size of the texture's memory in bytes
+	pub fn byte_size(&self) -> u64 {
+		self.byte_size
+	}
 	pub fn timeline_sync(&self) -> &TimelineSyncObj {
 		&self.sync_obj
 	}
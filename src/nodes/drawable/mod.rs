@@ -10,11 +10,11 @@ use super::{
 	spatial::{Spatial, Transform},
 };
 use crate::{
-	core::vulkano_data::VULKANO_CONTEXT,
+	core::vulkano_data::vulkano_context,
 	nodes::{drawable::dmatex::ALL_DRM_FOURCCS, spatial::SPATIAL_ASPECT_ALIAS_INFO},
 };
 use crate::{
-	core::{Id, client::Client, error::Result, resource::get_resource_file},
+	core::{Id, client::Client, error::Result},
 	nodes::drawable::dmatex::ImportedDmatex,
 };
 use color_eyre::eyre::eyre;
@@ -65,16 +65,17 @@ impl InterfaceAspect for Interface {
 		calling_client: Arc<Client>,
 		tex: Option<ResourceID>,
 	) -> Result<()> {
-		let resource_path = tex
-			.map(|tex| {
-				get_resource_file(
-					&tex,
-					calling_client.base_resource_prefixes.lock().iter(),
-					&[OsStr::new("hdr"), OsStr::new("png"), OsStr::new("jpg")],
-				)
-				.ok_or(eyre!("Could not find resource"))
-			})
-			.transpose()?;
+		let resource_path = match tex {
+			Some(tex) => Some(
+				calling_client
+					.get_resource_file(
+						&tex,
+						&[OsStr::new("hdr"), OsStr::new("png"), OsStr::new("jpg")],
+					)?
+					.ok_or(eyre!("Could not find resource"))?,
+			),
+			None => None,
+		};
 		QUEUED_SKYTEX.lock().replace(resource_path);
 		Ok(())
 	}
@@ -84,16 +85,17 @@ impl InterfaceAspect for Interface {
 		calling_client: Arc<Client>,
 		light: Option<ResourceID>,
 	) -> Result<()> {
-		let resource_path = light
-			.map(|light| {
-				get_resource_file(
-					&light,
-					calling_client.base_resource_prefixes.lock().iter(),
-					&[OsStr::new("hdr"), OsStr::new("png"), OsStr::new("jpg")],
-				)
-				.ok_or(eyre!("Could not find resource"))
-			})
-			.transpose()?;
+		let resource_path = match light {
+			Some(light) => Some(
+				calling_client
+					.get_resource_file(
+						&light,
+						&[OsStr::new("hdr"), OsStr::new("png"), OsStr::new("jpg")],
+					)?
+					.ok_or(eyre!("Could not find resource"))?,
+			),
+			None => None,
+		};
 		QUEUED_SKYLIGHT.lock().replace(resource_path);
 		Ok(())
 	}
@@ -164,6 +166,10 @@ impl InterfaceAspect for Interface {
 		planes: Vec<DmatexPlane>,
 		timeline_syncobj_fd: ProtocolFd,
 	) -> Result<()> {
+		calling_client.check_dmatex_quota(
+			dmatex_id,
+			ImportedDmatex::estimate_byte_size(&size, &planes),
+		)?;
 		let dmatex = ImportedDmatex::new(
 			size,
 			format,
@@ -197,6 +203,7 @@ impl InterfaceAspect for Interface {
 		let Some(tex) = ImportedDmatex::import_uid(dmatex_uid.0) else {
 			bail!("invalid dmatex id");
 		};
+		calling_client.check_dmatex_quota(dmatex_id, tex.byte_size())?;
 		calling_client.dmatexes.insert(dmatex_id, tex);
 		Ok(())
 	}
@@ -214,7 +221,7 @@ impl InterfaceAspect for Interface {
 		_node: Arc<Node>,
 		_calling_client: Arc<Client>,
 	) -> Result<Id> {
-		let vk = VULKANO_CONTEXT.wait();
+		let vk = vulkano_context()?;
 		let Some(id) = vk.get_drm_render_node_id() else {
 			bail!("unable to get render_node id");
 		};
@@ -226,7 +233,7 @@ impl InterfaceAspect for Interface {
 		_calling_client: Arc<Client>,
 		render_node_id: Id,
 	) -> Result<Vec<DmatexFormatInfo>> {
-		let vk = VULKANO_CONTEXT.wait();
+		let vk = vulkano_context()?;
 		if Some(render_node_id.0) != vk.get_drm_render_node_id() {
 			bail!(
 				"enumerating formats for devices other than the render_node used by the server is not implemented yet"
@@ -6,12 +6,12 @@ use crate::{
 		color::ColorConvert as _,
 		entity_handle::EntityHandle,
 	},
-	core::{Id, client::Client, error::Result, registry::Registry, resource::get_resource_file},
+	core::{Id, client::Client, error::Result, registry::Registry},
 	nodes::{
 		Node,
 		alias::{Alias, AliasList},
 		drawable::dmatex::{ImportedDmatex, SignalOnDrop},
-		spatial::{Spatial, SpatialNode},
+		spatial::{Spatial, SpatialNode, merge_aabbs, transform_aabb},
 	},
 };
 use bevy::{
@@ -273,8 +273,14 @@ fn gen_model_parts(
 					let (spatial, model_part) =
 						match model.pre_bound_parts.lock().iter().find(|v| v.path == path) {
 							None => {
-								let node =
-									client.scenegraph.add_node(Node::generate(&client, false));
+								let node = client
+									.scenegraph
+									.add_node(Node::generate(&client, false))
+									// the quota check already applied the violation policy
+									.inspect_err(|err| {
+										warn!(?client, "Couldn't create model part {path}: {err}")
+									})
+									.ok()?;
 								let spatial = Spatial::add_to(
 									&node,
 									Some(parent_spatial.clone()),
@@ -300,16 +306,13 @@ fn gen_model_parts(
 								(part.spatial.clone(), part.clone())
 							}
 						};
-					let aabb = Aabb::enclosing(
+					let aabb = merge_aabbs(
 						children
 							.iter()
 							.flat_map(|v| v.iter())
 							.filter_map(|e| part_mesh_query.get(e).ok())
-							.flat_map(|(transform, aabb)| {
-								[
-									transform.transform_point(aabb.min().into()),
-									transform.transform_point(aabb.max().into()),
-								]
+							.map(|(transform, aabb)| {
+								transform_aabb(*aabb, transform.compute_matrix())
 							}),
 					)
 					.unwrap_or_default();
@@ -469,6 +472,7 @@ fn hash_color<H: Hasher>(color: Color, state: &mut H) {
 	}
 }
 static MODEL_REGISTRY: Registry<Model> = Registry::new();
+const TEXTURE_EXTENSIONS: &[&OsStr] = &[OsStr::new("png"), OsStr::new("jpg")];
 
 impl MaterialParameter {
 	fn apply_to_material(
@@ -526,11 +530,8 @@ impl MaterialParameter {
 				}
 			},
 			MaterialParameter::Texture(resource) => {
-				let Some(texture_path) = get_resource_file(
-					resource,
-					client.base_resource_prefixes.lock().iter(),
-					&[OsStr::new("png"), OsStr::new("jpg")],
-				) else {
+				let Ok(Some(texture_path)) = client.get_resource_file(resource, TEXTURE_EXTENSIONS)
+				else {
 					return;
 				};
 				let handle = asset_server.load(texture_path);
@@ -675,11 +676,15 @@ impl ModelPartAspect for ModelPart {
 	#[doc = "Set the material parameter with `parameter_name` to `value`"]
 	fn set_material_parameter(
 		node: Arc<Node>,
-		_calling_client: Arc<Client>,
+		calling_client: Arc<Client>,
 		parameter_name: String,
 		value: MaterialParameter,
 	) -> Result<()> {
 		let model_part = node.get_aspect::<ModelPart>()?;
+		// the texture only gets loaded later on, so report a denied path now
+		if let MaterialParameter::Texture(resource) = &value {
+			calling_client.get_resource_file(resource, TEXTURE_EXTENSIONS)?;
+		}
 		model_part.set_material_parameter(parameter_name, value);
 		Ok(())
 	}
@@ -721,12 +726,9 @@ pub struct Model {
 impl Model {
 	pub fn add_to(node: &Arc<Node>, resource_id: ResourceID) -> Result<Arc<Model>> {
 		let client = node.get_client().ok_or_else(|| eyre!("Client not found"))?;
-		let pending_model_path = get_resource_file(
-			&resource_id,
-			client.base_resource_prefixes.lock().iter(),
-			&[OsStr::new("glb"), OsStr::new("gltf")],
-		)
-		.ok_or_else(|| eyre!("Resource not found"))?;
+		let pending_model_path = client
+			.get_resource_file(&resource_id, &[OsStr::new("glb"), OsStr::new("gltf")])?
+			.ok_or_else(|| eyre!("Resource not found"))?;
 
 		let model = Arc::new(Model {
 			spatial: node.get_aspect::<Spatial>().unwrap().clone(),
@@ -739,6 +741,8 @@ impl Model {
 		});
 		_ = model.spatial.bounding_box_calc.set(|n| {
 			Box::pin(async {
+				// the parts are child spatials with their own bounds, so this just has to wait
+				// for them to exist
 				if let Ok(model) = n.get_aspect::<Model>()
 					&& !model.setup_complete.load(Ordering::Relaxed)
 				{
@@ -747,9 +751,14 @@ impl Model {
 				Aabb::default()
 			})
 		});
-		LOAD_MODEL
+		if LOAD_MODEL
 			.send((model.clone(), pending_model_path))
-			.unwrap();
+			.is_none()
+		{
+			// nothing loads models when running headless
+			model.setup_complete.store(true, Ordering::Relaxed);
+			model.setup_complete_notify.notify_waiters();
+		}
 		MODEL_REGISTRY.add_raw(&model);
 
 		node.add_aspect_raw(model.clone());
@@ -776,7 +785,7 @@ impl Model {
 			}
 			None => {
 				let client = self.spatial.node().unwrap().get_client().unwrap();
-				let part_node = client.scenegraph.add_node(Node::generate(&client, false));
+				let part_node = client.scenegraph.add_node(Node::generate(&client, false))?;
 				let spatial =
 					Spatial::add_to(&part_node, Some(self.spatial.clone()), Mat4::IDENTITY);
 				let part = part_node.add_aspect(ModelPart {
@@ -5,14 +5,18 @@ use crate::{
 		color::ColorConvert,
 		entity_handle::EntityHandle,
 	},
-	core::{client::Client, error::Result, registry::Registry, resource::get_resource_file},
+	core::{client::Client, error::Result, registry::Registry},
 	nodes::{
 		Node,
 		drawable::{TextFit, XAlign},
-		spatial::{Spatial, SpatialNode},
+		spatial::{Spatial, SpatialNode, merge_aabbs},
 	},
 };
-use bevy::{platform::collections::HashMap, prelude::*};
+use bevy::{
+	platform::collections::HashMap,
+	prelude::*,
+	render::{mesh::VertexAttributeValues, primitives::Aabb},
+};
 use bevy_mesh_text_3d::{
 	Align, Attrs, HorizontalAnchorPoint, MeshTextPlugin, Settings as FontSettings, VerticalAlign,
 	VerticalAnchorPoint, generate_meshes,
@@ -20,9 +24,12 @@ use bevy_mesh_text_3d::{
 use color_eyre::eyre::eyre;
 use core::f32;
 use parking_lot::Mutex;
-use std::{ffi::OsStr, mem, path::PathBuf, sync::Arc};
+use std::{ffi::OsStr, mem, path::PathBuf, pin::pin, sync::Arc, time::Duration};
+use tokio::sync::Notify;
 
 static SPAWN_TEXT: BevyChannel<Arc<Text>> = BevyChannel::new();
+/// How long a bounding box query waits for the text meshes to be regenerated
+const BOUNDS_TIMEOUT: Duration = Duration::from_secs(1);
 
 pub struct TextNodePlugin;
 
@@ -131,8 +138,25 @@ fn spawn_text(
 		let Ok((char_meshes, _text_size)) =
 			char_meshes.inspect_err(|err| error!("unable to create text meshes: {err}"))
 		else {
+			text.set_bounds(Aabb::default());
 			continue;
 		};
+		// the glyph meshes are laid out by their transforms, so this is the layout's extent
+		let bounds = merge_aabbs(char_meshes.iter().filter_map(|char_mesh| {
+			let Some(VertexAttributeValues::Float32x3(positions)) = meshes
+				.get(&char_mesh.mesh)?
+				.attribute(Mesh::ATTRIBUTE_POSITION)
+			else {
+				return None;
+			};
+			Aabb::enclosing(
+				positions
+					.iter()
+					.map(|p| char_mesh.transform.transform_point(Vec3::from_slice(p))),
+			)
+		}))
+		.unwrap_or_default();
+		text.set_bounds(bounds);
 
 		let letters = char_meshes
 			.into_iter()
@@ -178,29 +202,65 @@ pub struct Text {
 	entity: Mutex<Option<EntityHandle>>,
 	text: Mutex<String>,
 	data: Mutex<TextStyle>,
+	/// `None` while the meshes are being regenerated
+	bounds: Mutex<Option<Aabb>>,
+	bounds_updated: Notify,
 }
 impl Text {
 	pub fn add_to(node: &Arc<Node>, text: String, style: TextStyle) -> Result<Arc<Text>> {
 		let client = node.get_client().ok_or_else(|| eyre!("Client not found"))?;
 		let text = TEXT_REGISTRY.add(Text {
 			spatial: node.get_aspect::<Spatial>().unwrap().clone(),
-			font_path: style.font.as_ref().and_then(|res| {
-				get_resource_file(
-					res,
-					client.base_resource_prefixes.lock().iter(),
-					&[OsStr::new("ttf"), OsStr::new("otf")],
-				)
-			}),
+			font_path: style
+				.font
+				.as_ref()
+				.map(|res| client.get_resource_file(res, &[OsStr::new("ttf"), OsStr::new("otf")]))
+				.transpose()?
+				.flatten(),
 
 			entity: Mutex::new(None),
 			text: Mutex::new(text),
 			data: Mutex::new(style),
+			bounds: Mutex::new(None),
+			bounds_updated: Notify::new(),
+		});
+		_ = node.get_aspect::<Spatial>()?.bounding_box_calc.set(|node| {
+			Box::pin(async {
+				let Ok(text) = node.get_aspect::<Text>() else {
+					return Aabb::default();
+				};
+				// enabled before checking so an update in between isn't missed
+				let mut updated = pin!(text.bounds_updated.notified());
+				updated.as_mut().enable();
+				let bounds = *text.bounds.lock();
+				match bounds {
+					Some(aabb) => aabb,
+					None => {
+						// don't hold up the bounding box if the meshes never get generated
+						let _ = tokio::time::timeout(BOUNDS_TIMEOUT, updated).await;
+						text.bounds.lock().unwrap_or_default()
+					}
+				}
+			})
 		});
 		node.add_aspect_raw(text.clone());
-		_ = SPAWN_TEXT.send(text.clone());
+		text.respawn();
 
 		Ok(text)
 	}
+
+	/// Regenerate the meshes, and with them the bounds
+	fn respawn(self: &Arc<Self>) {
+		*self.bounds.lock() = None;
+		if SPAWN_TEXT.send(self.clone()).is_none() {
+			// nothing generates text meshes when running headless
+			self.set_bounds(Aabb::default());
+		}
+	}
+	fn set_bounds(&self, bounds: Aabb) {
+		*self.bounds.lock() = Some(bounds);
+		self.bounds_updated.notify_waiters();
+	}
 }
 impl TextAspect for Text {
 	fn set_character_height(
@@ -210,14 +270,14 @@ impl TextAspect for Text {
 	) -> Result<()> {
 		let this_text = node.get_aspect::<Text>()?;
 		this_text.data.lock().character_height = height;
-		_ = SPAWN_TEXT.send(this_text);
+		this_text.respawn();
 		Ok(())
 	}
 
 	fn set_text(node: Arc<Node>, _calling_client: Arc<Client>, text: String) -> Result<()> {
 		let this_text = node.get_aspect::<Text>()?;
 		*this_text.text.lock() = text;
-		_ = SPAWN_TEXT.send(this_text);
+		this_text.respawn();
 		Ok(())
 	}
 }
@@ -0,0 +1,175 @@
+use super::Node;
+use crate::core::{Id, client::Client};
+use dashmap::DashMap;
+use std::{
+	sync::{Arc, LazyLock, Weak},
+	time::Instant,
+};
+
+/// Restrictions on who can import an exported node and for how long
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ExportRules {
+	/// The export is removed the first time it's imported
+	pub one_shot: bool,
+	/// The export is removed at this point
+	pub expires_at: Option<Instant>,
+	/// Only the client with this ID may import it, unlike pids these are never reused
+	pub target_client: Option<u64>,
+}
+
+struct Export {
+	node: Weak<Node>,
+	rules: ExportRules,
+}
+impl Export {
+	fn valid(&self, now: Instant) -> bool {
+		self.node.strong_count() > 0
+			&& self
+				.rules
+				.expires_at
+				.is_none_or(|expires_at| now < expires_at)
+	}
+}
+
+/// Nodes that clients have handed out a UID for so that other clients can import them.
+/// Exports are removed once they're revoked, expire or get used up.
+/// Exports of destroyed nodes are pruned lazily so dropping a node doesn't have to scan every export.
+// uses dashmap because it's concurrent and doesn't use fxhash because the UIDs are random anyway
+pub struct ExportRegistry(LazyLock<DashMap<u64, Export>>);
+impl ExportRegistry {
+	pub const fn new() -> Self {
+		ExportRegistry(LazyLock::new(DashMap::new))
+	}
+
+	pub fn export(&self, node: &Arc<Node>, rules: ExportRules) -> Id {
+		self.remove_invalid();
+		let uid = loop {
+			let uid = rand::random();
+			if !self.0.contains_key(&uid) {
+				break uid;
+			}
+		};
+		self.0.insert(
+			uid,
+			Export {
+				node: Arc::downgrade(node),
+				rules,
+			},
+		);
+		Id(uid)
+	}
+
+	/// Get the node for a client importing it, enforcing the export's rules
+	pub fn import(&self, uid: Id, client: &Client) -> Option<Arc<Node>> {
+		let now = Instant::now();
+		let entry = self.0.get(&uid.0)?;
+		if !entry.valid(now) {
+			drop(entry);
+			self.0.remove(&uid.0);
+			return None;
+		}
+		if entry
+			.rules
+			.target_client
+			.is_some_and(|target_client| client.id != target_client)
+		{
+			return None;
+		}
+		let node = entry.node.upgrade();
+		let one_shot = entry.rules.one_shot;
+		drop(entry);
+		if one_shot {
+			// another import might have used it up in the meantime
+			self.0.remove(&uid.0)?;
+		}
+		node
+	}
+
+	/// Look up an exported node without any of the import rules, for the server's own use
+	pub fn get(&self, uid: u64) -> Option<Arc<Node>> {
+		let entry = self.0.get(&uid)?;
+		entry
+			.valid(Instant::now())
+			.then(|| entry.node.upgrade())
+			.flatten()
+	}
+
+	/// Remove an export, only the client that owns the exported node may do this
+	pub fn revoke(&self, uid: Id, client: &Client) -> bool {
+		// upgrade outside of remove_if, dropping the node with the shard locked would deadlock
+		let Some(node) = self.0.get(&uid.0).and_then(|export| export.node.upgrade()) else {
+			return false;
+		};
+		if node.get_client().is_none_or(|owner| owner.id != client.id) {
+			return false;
+		}
+		let node = Arc::downgrade(&node);
+		self.0
+			.remove_if(&uid.0, |_, export| export.node.ptr_eq(&node))
+			.is_some()
+	}
+
+	fn remove_invalid(&self) {
+		let now = Instant::now();
+		self.0.retain(|_, export| export.valid(now));
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::core::test_client::TestClient;
+
+	#[tokio::test]
+	async fn export_rules() {
+		let exporter = TestClient::connect().await;
+		let importer = TestClient::connect().await;
+		let node = Node::generate(&exporter.client, true)
+			.add_to_scenegraph()
+			.unwrap();
+		let exports = ExportRegistry::new();
+
+		let uid = exports.export(&node, ExportRules::default());
+		assert!(exports.import(uid, &importer.client).is_some());
+		assert!(exports.import(uid, &importer.client).is_some());
+		assert!(!exports.revoke(uid, &importer.client));
+		assert!(exports.revoke(uid, &exporter.client));
+		assert!(exports.import(uid, &importer.client).is_none());
+
+		let one_shot = ExportRules {
+			one_shot: true,
+			..Default::default()
+		};
+		let uid = exports.export(&node, one_shot);
+		assert!(exports.import(uid, &importer.client).is_some());
+		assert!(exports.import(uid, &importer.client).is_none());
+
+		let other_target = ExportRules {
+			target_client: Some(exporter.client.id),
+			..Default::default()
+		};
+		let uid = exports.export(&node, other_target);
+		assert!(exports.import(uid, &importer.client).is_none());
+		// the server itself can still use it
+		assert!(exports.get(uid.0).is_some());
+		let own_target = ExportRules {
+			target_client: Some(importer.client.id),
+			..Default::default()
+		};
+		let uid = exports.export(&node, own_target);
+		assert!(exports.import(uid, &importer.client).is_some());
+
+		let expired = ExportRules {
+			expires_at: Some(Instant::now()),
+			..Default::default()
+		};
+		let uid = exports.export(&node, expired);
+		assert!(exports.import(uid, &importer.client).is_none());
+
+		let uid = exports.export(&node, ExportRules::default());
+		node.destroy();
+		drop(node);
+		assert!(!exports.revoke(uid, &exporter.client));
+		assert!(exports.import(uid, &importer.client).is_none());
+	}
+}
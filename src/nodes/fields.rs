@@ -1,4 +1,5 @@
 use super::alias::{Alias, AliasInfo};
+use super::export::{ExportRegistry, ExportRules};
 use super::spatial::{
 	SPATIAL_REF_GET_LOCAL_BOUNDING_BOX_SERVER_OPCODE,
 	SPATIAL_REF_GET_RELATIVE_BOUNDING_BOX_SERVER_OPCODE, SPATIAL_REF_GET_TRANSFORM_SERVER_OPCODE,
@@ -12,7 +13,8 @@ use crate::core::error::Result;
 use crate::core::registry::Registry;
 use crate::nodes::spatial::SPATIAL_ASPECT_ALIAS_INFO;
 use crate::nodes::spatial::SPATIAL_REF_ASPECT_ALIAS_INFO;
-use crate::nodes::spatial::Transform;
+use crate::nodes::spatial::{ExportOptions, Transform};
+use crate::objects::object_path;
 use bevy::app::{Plugin, Update};
 use bevy::asset::Assets;
 use bevy::color::Color;
@@ -24,12 +26,12 @@ use bevy::ecs::system::{Commands, Query, Res, ResMut};
 use bevy::gizmos::GizmoAsset;
 use bevy::gizmos::retained::Gizmo;
 use color_eyre::eyre::OptionExt;
-use dashmap::DashMap;
 use glam::{Vec3, Vec3A, Vec3Swizzles, vec2, vec3, vec3a};
 use parking_lot::Mutex;
+use stardust_xr_server_foundation::bail;
 use stardust_xr_wire::values::Vector3;
 use std::collections::{HashMap, HashSet};
-use std::sync::{Arc, LazyLock, Weak};
+use std::sync::{Arc, LazyLock};
 use zbus::interface;
 
 // TODO: get SDFs working properly with non-uniform scale and so on, output distance relative to the spatial it's compared against
@@ -55,7 +57,7 @@ impl Plugin for FieldDebugGizmoPlugin {
 		tokio::spawn(async move {
 			_ = conn
 				.object_server()
-				.at("/org/stardustxr/Server", FieldDebugGizmos { state: tx })
+				.at(object_path("Server"), FieldDebugGizmos { state: tx })
 				.await;
 		});
 		app.insert_resource(FieldDebugGizmosEnabled(rx));
@@ -530,7 +532,7 @@ impl CubicSplineShape {
 	}
 }
 
-pub static EXPORTED_FIELDS: LazyLock<DashMap<u64, Weak<Node>>> = LazyLock::new(DashMap::new);
+pub static EXPORTED_FIELDS: ExportRegistry = ExportRegistry::new();
 
 pub trait FieldTrait: Send + Sync + 'static {
 	fn spatial_ref(&self) -> &Spatial;
@@ -667,9 +669,16 @@ impl FieldAspect for Field {
 	}
 
 	async fn export_field(node: Arc<Node>, _calling_client: Arc<Client>) -> Result<Id> {
-		let id = rand::random();
-		EXPORTED_FIELDS.insert(id, Arc::downgrade(&node));
-		Ok(id.into())
+		Ok(EXPORTED_FIELDS.export(&node, ExportRules::default()))
+	}
+
+	#[doc = "Like `export_field`, but the UID can be limited to one import, a target client and a lifetime"]
+	async fn export_field_with_options(
+		node: Arc<Node>,
+		_calling_client: Arc<Client>,
+		options: ExportOptions,
+	) -> Result<Id> {
+		Ok(EXPORTED_FIELDS.export(&node, options.try_into()?))
 	}
 }
 impl FieldTrait for Field {
@@ -768,8 +777,7 @@ impl InterfaceAspect for Interface {
 		uid: Id,
 	) -> Result<Id> {
 		let node = EXPORTED_FIELDS
-			.get(&uid.0)
-			.and_then(|s| s.upgrade())
+			.import(uid, &calling_client)
 			.map(|s| {
 				Alias::create(
 					&s,
@@ -783,6 +791,14 @@ impl InterfaceAspect for Interface {
 		Ok(node.get_id())
 	}
 
+	#[doc = "Stop a UID from `export_field` from being imported. Only the client that exported it can revoke it."]
+	fn revoke_field_export(_node: Arc<Node>, calling_client: Arc<Client>, uid: Id) -> Result<()> {
+		if !EXPORTED_FIELDS.revoke(uid, &calling_client) {
+			bail!("Couldn't find an export with that ID that this client can revoke");
+		}
+		Ok(())
+	}
+
 	fn create_field(
 		_node: Arc<Node>,
 		calling_client: Arc<Client>,
@@ -803,6 +819,7 @@ impl InterfaceAspect for Interface {
 #[cfg(test)]
 mod tests {
 	use super::*;
+	use crate::core::test_client::TestClient;
 
 	fn make_spline(points: &[([f32; 3], [f32; 3], [f32; 3], f32)], cyclic: bool) -> CubicSplineShape {
 		CubicSplineShape {
@@ -870,4 +887,47 @@ mod tests {
 			assert!(d < 0.0, "point on curve should be inside tube, t={t}, sd={d}");
 		}
 	}
+
+	#[tokio::test]
+	async fn field_distance_over_protocol() {
+		let client = TestClient::connect().await;
+		let field = client.new_id();
+		client.signal(
+			INTERFACE_NODE_ID,
+			Interface::ID,
+			INTERFACE_CREATE_FIELD_SERVER_OPCODE,
+			(
+				field,
+				Id(0),
+				Transform {
+					translation: Some([0.0, 0.0, 1.0].into()),
+					rotation: None,
+					scale: None,
+				},
+				Shape::Sphere(0.5),
+			),
+		);
+		let distance: f32 = client
+			.method(
+				field,
+				FieldRef::ID,
+				FIELD_REF_DISTANCE_SERVER_OPCODE,
+				(Id(0), Vector3::from([0.0_f32; 3])),
+			)
+			.await
+			.unwrap();
+		assert!((distance - 0.5).abs() < 0.0001, "got {distance}");
+
+		// a point inside the sphere is negative
+		let distance: f32 = client
+			.method(
+				field,
+				FieldRef::ID,
+				FIELD_REF_DISTANCE_SERVER_OPCODE,
+				(Id(0), Vector3::from([0.0, 0.0, 1.25_f32])),
+			)
+			.await
+			.unwrap();
+		assert!((distance + 0.25).abs() < 0.0001, "got {distance}");
+	}
 }
@@ -109,3 +109,95 @@ impl InterfaceAspect for Interface {
 		Ok(())
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::core::test_client::TestClient;
+	use crate::nodes::fields::Shape;
+	use glam::Mat4;
+	use serde::Serialize;
+
+	#[derive(Serialize)]
+	struct TestDatamap {
+		select: f32,
+	}
+
+	fn identity() -> Transform {
+		Transform {
+			translation: None,
+			rotation: None,
+			scale: None,
+		}
+	}
+
+	#[tokio::test]
+	async fn input_routed_to_handler() {
+		let client = TestClient::connect().await;
+		let root = client.client.scenegraph.get_node(Id(0)).unwrap();
+
+		// a sphere field 0.5m in front of the input method
+		let field = client.new_id();
+		let field_node = Node::from_id(&client.client, field, true)
+			.add_to_scenegraph()
+			.unwrap();
+		Spatial::add_to(
+			&field_node,
+			Some(root.get_aspect::<Spatial>().unwrap()),
+			Mat4::from_translation([0.0, 0.0, -1.0].into()),
+		);
+		Field::add_to(&field_node, Shape::Sphere(0.5)).unwrap();
+
+		let handler = client.new_id();
+		client.signal(
+			INTERFACE_NODE_ID,
+			Interface::ID,
+			INTERFACE_CREATE_INPUT_HANDLER_SERVER_OPCODE,
+			(handler, Id(0), identity(), field),
+		);
+		let method = client.new_id();
+		client.signal(
+			INTERFACE_NODE_ID,
+			Interface::ID,
+			INTERFACE_CREATE_INPUT_METHOD_SERVER_OPCODE,
+			(
+				method,
+				Id(0),
+				identity(),
+				InputDataType::Tip(Tip::default()),
+				Datamap::from_typed(TestDatamap { select: 1.0 }).unwrap(),
+			),
+		);
+		client.signal(
+			method,
+			InputMethod::ID,
+			INPUT_METHOD_SET_HANDLER_ORDER_SERVER_OPCODE,
+			vec![handler],
+		);
+
+		// the method's client is told about the handler and its field
+		let create_handler = client
+			.wait_for_signal(|s| {
+				s.node == method.0 && s.method == INPUT_METHOD_CREATE_HANDLER_CLIENT_OPCODE
+			})
+			.await;
+		let (handler_alias, _field_alias): (Id, Id) = create_handler.args();
+		let handler_alias = client.client.scenegraph.get_node(handler_alias).unwrap();
+		assert_eq!(handler_alias.get_id(), handler);
+
+		// and the handler gets the input with the distance to its field
+		let input_sent = client
+			.wait_for_signal(|s| {
+				s.node == handler.0 && s.method == INPUT_HANDLER_INPUT_SENT_CLIENT_OPCODE
+			})
+			.await;
+		let (_method_alias, data): (Id, InputData) = input_sent.args();
+		assert!(
+			(data.distance - 0.5).abs() < 0.0001,
+			"got {}",
+			data.distance
+		);
+		assert_eq!(data.order, 0);
+		assert!(!data.captured);
+	}
+}
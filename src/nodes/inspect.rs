@@ -0,0 +1,133 @@
+use super::{Node, alias::Alias, spatial::Spatial};
+use crate::{
+	DbusConnection,
+	core::{
+		Id,
+		client::{CLIENTS, Client},
+	},
+	objects::object_path,
+};
+use bevy::app::{App, Plugin};
+use std::sync::Arc;
+use zbus::{fdo, interface};
+
+/// Lets tools like scenegraph inspectors see every client's nodes over DBus
+pub struct ScenegraphInspectorPlugin;
+impl Plugin for ScenegraphInspectorPlugin {
+	fn build(&self, app: &mut App) {
+		let conn = app.world().resource::<DbusConnection>().0.clone();
+		tokio::spawn(async move {
+			_ = conn
+				.object_server()
+				.at(object_path("Server"), ScenegraphInspector)
+				.await;
+		});
+	}
+}
+
+/// Nodes are referred to by the ID of the client that owns them along with their node ID.
+struct ScenegraphInspector;
+#[interface(name = "org.stardustxr.debug.Scenegraph")]
+impl ScenegraphInspector {
+	/// Every connected client as (client ID, pid or -1, executable path, node count, alias count)
+	fn clients(&self) -> Vec<(u64, i32, String, u32, u32)> {
+		CLIENTS
+			.get_vec()
+			.into_iter()
+			.map(|client| {
+				(
+					client.id,
+					client.pid.unwrap_or(-1),
+					client
+						.exe()
+						.map(|exe| exe.to_string_lossy().into_owned())
+						.unwrap_or_default(),
+					client.scenegraph.node_count() as u32,
+					client.quota.alias_count() as u32,
+				)
+			})
+			.collect()
+	}
+
+	/// Every node of a client as (node ID, aspect IDs, enabled)
+	fn nodes(&self, client_id: u64) -> fdo::Result<Vec<(u64, Vec<u64>, bool)>> {
+		let client = find_client(client_id)?;
+		let mut nodes = client
+			.scenegraph
+			.nodes()
+			.into_iter()
+			.map(|node| (node.get_id().0, node.aspect_ids(), node.enabled()))
+			.collect::<Vec<_>>();
+		nodes.sort_unstable_by_key(|(id, _, _)| *id);
+		Ok(nodes)
+	}
+
+	/// The nodes other clients hold as aliases of this node
+	fn aliases(&self, client_id: u64, node_id: u64) -> fdo::Result<Vec<(u64, u64)>> {
+		let node = find_node(client_id, node_id)?;
+		Ok(node
+			.aliases
+			.get_valid_contents()
+			.into_iter()
+			.filter_map(|alias| node_ref(&alias.node.upgrade()?))
+			.collect())
+	}
+
+	/// The node this alias points to
+	fn alias_original(&self, client_id: u64, node_id: u64) -> fdo::Result<(u64, u64)> {
+		let node = find_node(client_id, node_id)?;
+		let alias = node
+			.get_aspect::<Alias>()
+			.map_err(|_| fdo::Error::InvalidArgs("Node is not an alias".to_string()))?;
+		alias
+			.original
+			.upgrade()
+			.as_ref()
+			.and_then(node_ref)
+			.ok_or_else(|| fdo::Error::Failed("Original node no longer exists".to_string()))
+	}
+
+	/// The node this node's spatial is parented to
+	fn spatial_parent(&self, client_id: u64, node_id: u64) -> fdo::Result<(u64, u64)> {
+		let spatial = find_spatial(client_id, node_id)?;
+		spatial
+			.get_parent()
+			.and_then(|parent| parent.node())
+			.as_ref()
+			.and_then(node_ref)
+			.ok_or_else(|| fdo::Error::Failed("Spatial has no parent".to_string()))
+	}
+
+	/// The column-major global transform matrix of a spatial node
+	fn global_transform(&self, client_id: u64, node_id: u64) -> fdo::Result<Vec<f64>> {
+		let spatial = find_spatial(client_id, node_id)?;
+		Ok(spatial
+			.global_transform()
+			.to_cols_array()
+			.into_iter()
+			.map(f64::from)
+			.collect())
+	}
+}
+
+fn find_client(client_id: u64) -> fdo::Result<Arc<Client>> {
+	CLIENTS
+		.get_vec()
+		.into_iter()
+		.find(|client| client.id == client_id)
+		.ok_or_else(|| fdo::Error::InvalidArgs(format!("No client with ID {client_id}")))
+}
+fn find_node(client_id: u64, node_id: u64) -> fdo::Result<Arc<Node>> {
+	find_client(client_id)?
+		.scenegraph
+		.get_node_raw(Id(node_id))
+		.ok_or_else(|| fdo::Error::InvalidArgs(format!("No node with ID {node_id}")))
+}
+fn find_spatial(client_id: u64, node_id: u64) -> fdo::Result<Arc<Spatial>> {
+	find_node(client_id, node_id)?
+		.get_aspect::<Spatial>()
+		.map_err(|_| fdo::Error::InvalidArgs("Node is not a spatial".to_string()))
+}
+fn node_ref(node: &Arc<Node>) -> Option<(u64, u64)> {
+	Some((node.get_client()?.id, node.get_id().0))
+}
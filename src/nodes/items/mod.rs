@@ -338,12 +338,12 @@ impl ItemAcceptor {
 	}
 	fn handle_release(&self, item: &Item) {
 		self.accepted_registry.remove(item);
+		let alias = self.accepted_aliases.get_from_aspect(item);
 		self.accepted_aliases.remove_aspect(item);
 
-		let Some(node) = self.spatial.node() else {
+		let (Some(node), Some(alias)) = (self.spatial.node(), alias) else {
 			return;
 		};
-		let alias = self.accepted_aliases.get_from_aspect(item).unwrap();
 		let _ = item_acceptor_client::release_item(&node, alias.id);
 	}
 }
@@ -46,10 +46,10 @@ pub static ITEM_TYPE_INFO_PANEL: LazyLock<TypeInfo> = LazyLock::new(|| TypeInfo
 	items: Registry::new(),
 	acceptors: Registry::new(),
 	add_acceptor_aspect: |node| {
-		node.add_aspect(PanelItemUi);
+		node.add_aspect(PanelItemAcceptor);
 	},
 	add_ui_aspect: |node| {
-		node.add_aspect(PanelItemAcceptor);
+		node.add_aspect(PanelItemUi);
 	},
 	new_acceptor_fn: |node, acceptor, acceptor_field| {
 		let _ = panel_item_ui_client::create_acceptor(node, acceptor, acceptor_field);
@@ -487,14 +487,13 @@ impl InterfaceAspect for Interface {
 
 	#[doc = "Create an item acceptor to allow temporary ownership of a given type of item. Creates a node at `/item/<item_type>/acceptor/<name>`."]
 	fn create_panel_item_acceptor(
-		node: Arc<Node>,
+		_node: Arc<Node>,
 		calling_client: Arc<Client>,
 		id: Id,
 		parent: Arc<Node>,
 		transform: Transform,
 		field: Arc<Node>,
 	) -> Result<()> {
-		node.add_aspect(PanelItemAcceptor);
 		create_item_acceptor_flex(
 			calling_client,
 			id,
@@ -537,3 +536,159 @@ impl InterfaceAspect for Interface {
 		Ok(keymap.clone())
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::core::test_client::{ReceivedSignal, TestClient};
+	use crate::nodes::{
+		alias::Alias,
+		fields::{self, INTERFACE_CREATE_FIELD_SERVER_OPCODE, Shape},
+		items::{
+			ITEM_ACCEPTOR_RELEASE_ITEM_CLIENT_OPCODE, ITEM_RELEASE_SERVER_OPCODE,
+			ITEM_UI_CAPTURE_ITEM_CLIENT_OPCODE, ITEM_UI_RELEASE_ITEM_CLIENT_OPCODE, ItemAcceptor,
+			ItemUI,
+		},
+	};
+
+	#[derive(Debug)]
+	struct TestBackend;
+	impl Backend for TestBackend {
+		fn start_data(&self) -> Result<PanelItemInitData> {
+			bail!("Test panels have no surfaces")
+		}
+		fn apply_cursor_material(&self, _model_part: &Arc<ModelPart>) {}
+		fn apply_surface_material(&self, _surface: SurfaceId, _model_part: &Arc<ModelPart>) {}
+		fn close_toplevel(&self) {}
+		fn auto_size_toplevel(&self) {}
+		fn set_toplevel_size(&self, _size: Vector2<u32>) {}
+		fn set_toplevel_focused_visuals(&self, _focused: bool) {}
+		fn absolute_pointer_motion(&self, _surface: &SurfaceId, _position: Vector2<f32>) {}
+		fn relative_pointer_motion(&self, _surface: &SurfaceId, _delta: Vector2<f32>) {}
+		fn pointer_button(&self, _surface: &SurfaceId, _button: u32, _pressed: bool) {}
+		fn pointer_scroll(
+			&self,
+			_surface: &SurfaceId,
+			_scroll_distance: Option<Vector2<f32>>,
+			_scroll_steps: Option<Vector2<f32>>,
+		) {
+		}
+		fn keyboard_key(&self, _surface: &SurfaceId, _keymap_id: Id, _key: u32, _pressed: bool) {}
+		fn touch_down(&self, _surface: &SurfaceId, _id: u32, _position: Vector2<f32>) {}
+		fn touch_move(&self, _id: u32, _position: Vector2<f32>) {}
+		fn touch_up(&self, _id: u32) {}
+		fn reset_input(&self) {}
+	}
+
+	#[tokio::test]
+	async fn capture_and_release() {
+		let ui = TestClient::connect().await;
+		ui.signal(
+			INTERFACE_NODE_ID,
+			Interface::ID,
+			INTERFACE_REGISTER_PANEL_ITEM_UI_SERVER_OPCODE,
+			(),
+		);
+		ui.sync().await;
+		let (item_node, _panel_item) = PanelItem::create(Box::new(TestBackend), None);
+		let item = item_node.get_aspect::<Item>().unwrap();
+
+		let acceptor_client = TestClient::connect().await;
+		let field_interface = acceptor_client
+			.client
+			.scenegraph
+			.nodes()
+			.into_iter()
+			.find(|node| node.get_aspect::<fields::Interface>().is_ok())
+			.unwrap()
+			.get_id();
+		let field = acceptor_client.new_id();
+		let acceptor = acceptor_client.new_id();
+		let transform = Transform {
+			translation: None,
+			rotation: None,
+			scale: None,
+		};
+		acceptor_client.signal(
+			field_interface,
+			fields::Interface::ID,
+			INTERFACE_CREATE_FIELD_SERVER_OPCODE,
+			(field, Id(0), transform.clone(), Shape::Sphere(0.5)),
+		);
+		acceptor_client.signal(
+			INTERFACE_NODE_ID,
+			Interface::ID,
+			INTERFACE_CREATE_PANEL_ITEM_ACCEPTOR_SERVER_OPCODE,
+			(acceptor, Id(0), transform, field),
+		);
+		acceptor_client.sync().await;
+		let (acceptor_alias, _field_alias): (Id, Id) = ui
+			.wait_for_signal(|signal| {
+				signal.aspect == PanelItemUi::ID
+					&& signal.method == PANEL_ITEM_UI_CREATE_ACCEPTOR_CLIENT_OPCODE
+			})
+			.await
+			.args();
+		let ui_item_alias = ITEM_TYPE_INFO_PANEL
+			.ui
+			.lock()
+			.upgrade()
+			.unwrap()
+			.item_aliases
+			.get_from_original_node(Arc::downgrade(&item_node))
+			.unwrap()
+			.get_id();
+		// normally the acceptor client gets this alias from the item UI or another acceptor
+		let item_alias = Alias::create(
+			&item_node,
+			&acceptor_client.client,
+			PANEL_ITEM_ASPECT_ALIAS_INFO.clone() + ITEM_ASPECT_ALIAS_INFO.clone(),
+			None,
+		)
+		.unwrap()
+		.get_id();
+
+		acceptor_client.signal(
+			acceptor,
+			PanelItemAcceptor::ID,
+			PANEL_ITEM_ACCEPTOR_CAPTURE_ITEM_SERVER_OPCODE,
+			item_alias,
+		);
+		acceptor_client.sync().await;
+		let acceptor_aspect = acceptor_client
+			.client
+			.scenegraph
+			.get_node(acceptor)
+			.unwrap()
+			.get_aspect::<ItemAcceptor>()
+			.unwrap();
+		assert!(std::ptr::eq(
+			item.captured_acceptor.lock().as_ptr(),
+			Arc::as_ptr(&acceptor_aspect)
+		));
+		let is_ui_signal = |method| {
+			move |signal: &ReceivedSignal| signal.aspect == ItemUI::ID && signal.method == method
+		};
+		let captured: (Id, Id) = ui
+			.wait_for_signal(is_ui_signal(ITEM_UI_CAPTURE_ITEM_CLIENT_OPCODE))
+			.await
+			.args();
+		assert_eq!(captured, (ui_item_alias, acceptor_alias));
+
+		acceptor_client.signal(item_alias, Item::ID, ITEM_RELEASE_SERVER_OPCODE, ());
+		acceptor_client.sync().await;
+		assert_eq!(item.captured_acceptor.lock().strong_count(), 0);
+		let released: (Id, Id) = ui
+			.wait_for_signal(is_ui_signal(ITEM_UI_RELEASE_ITEM_CLIENT_OPCODE))
+			.await
+			.args();
+		assert_eq!(released, (ui_item_alias, acceptor_alias));
+		acceptor_client
+			.wait_for_signal(|signal| {
+				signal.node == acceptor.0
+					&& signal.aspect == ItemAcceptor::ID
+					&& signal.method == ITEM_ACCEPTOR_RELEASE_ITEM_CLIENT_OPCODE
+			})
+			.await;
+	}
+}
@@ -1,16 +1,23 @@
 pub mod alias;
 pub mod audio;
 pub mod drawable;
+pub mod export;
 pub mod fields;
 pub mod input;
+pub mod inspect;
 pub mod items;
 pub mod root;
 pub mod spatial;
+pub mod transform_subscription;
+pub mod tween;
 pub mod camera;
 
 use self::alias::Alias;
-use crate::core::client::Client;
+use crate::core::client::{Client, RECORDING_DIR};
 use crate::core::error::{Result, ServerError};
+use crate::core::metrics::MessageKind;
+use crate::core::permissions::Permission;
+use crate::core::recording::RecordedMessageKind;
 use crate::core::registry::Registry;
 use crate::core::{Id, MethodResponseSender};
 use dashmap::DashMap;
@@ -24,6 +31,7 @@ use std::fmt::Debug;
 use std::os::fd::OwnedFd;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Weak};
+use std::time::Instant;
 use std::vec::Vec;
 use tracing::error;
 
@@ -111,18 +119,17 @@ impl Node {
 		node
 	}
 	pub fn add_to_scenegraph(self) -> Result<Arc<Node>> {
-		Ok(self
-			.get_client()
+		self.get_client()
 			.ok_or(ServerError::NoClient)?
 			.scenegraph
-			.add_node(self))
+			.add_node(self)
 	}
 	pub fn add_to_scenegraph_owned(self) -> Result<OwnedNode> {
 		Ok(OwnedNode(
 			self.get_client()
 				.ok_or(ServerError::NoClient)?
 				.scenegraph
-				.add_node(self),
+				.add_node(self)?,
 		))
 	}
 	pub fn enabled(&self) -> bool {
@@ -168,6 +175,11 @@ impl Node {
 	pub fn get_aspect<A: AspectIdentifier>(&self) -> Result<Arc<A>> {
 		self.aspects.get()
 	}
+	pub fn aspect_ids(&self) -> Vec<u64> {
+		let mut ids = self.aspects.0.iter().map(|a| *a.key()).collect::<Vec<_>>();
+		ids.sort_unstable();
+		ids
+	}
 
 	pub fn send_local_signal(
 		self: Arc<Self>,
@@ -192,6 +204,7 @@ impl Node {
 				.get(&aspect_id)
 				.ok_or(ScenegraphError::AspectNotFound)?
 				.clone();
+			self.check_permission(&calling_client, aspect_id, method)?;
 			aspect
 				.run_signal(calling_client, self.clone(), method, message)
 				.map_err(|error| ScenegraphError::MemberError {
@@ -222,9 +235,28 @@ impl Node {
 				response.send_err(ScenegraphError::AspectNotFound);
 				return;
 			};
+			if let Err(e) = self.check_permission(&calling_client, aspect_id, method) {
+				response.send_err(e);
+				return;
+			}
 			aspect.run_method(calling_client, self.clone(), method, message, response);
 		}
 	}
+	fn check_permission(
+		&self,
+		calling_client: &Client,
+		aspect_id: u64,
+		member: u64,
+	) -> Result<(), ScenegraphError> {
+		match Permission::required(self, aspect_id, member) {
+			Some(permission) if !calling_client.has_permission(permission) => {
+				Err(ScenegraphError::MemberError {
+					error: format!("This client doesn't have the {permission} permission"),
+				})
+			}
+			_ => Ok(()),
+		}
+	}
 	pub fn send_remote_signal(
 		&self,
 		aspect_id: u64,
@@ -260,7 +292,35 @@ impl Node {
 					},
 				);
 			});
+		self.send_remote_signal_to_owner(aspect_id, method, message)
+	}
+	/// Send a signal only to the client that owns this node, skipping its aliases
+	pub fn send_remote_signal_to_owner(
+		&self,
+		aspect_id: u64,
+		method: u64,
+		message: impl Into<Message>,
+	) -> Result<()> {
+		let message = message.into();
 		if let Some(handle) = self.message_sender_handle.as_ref() {
+			if let Some(client) = self.get_client() {
+				client.metrics.message(
+					MessageKind::SentSignal,
+					aspect_id,
+					method,
+					message.data.len(),
+				);
+				if RECORDING_DIR.get().is_some() {
+					client.record(
+						RecordedMessageKind::RemoteSignal,
+						self.id.0,
+						aspect_id,
+						method,
+						&message.data,
+						message.fds.len(),
+					);
+				}
+			}
 			handle.signal(self.id.0, aspect_id, method, &message.data, message.fds)?;
 		}
 		Ok(())
@@ -277,10 +337,22 @@ impl Node {
 			.ok_or(ServerError::NoMessenger)?;
 
 		let (serialized, fds) = serialize(input)?;
+		let client = self.get_client();
+		if let Some(client) = &client {
+			client
+				.metrics
+				.message(MessageKind::SentMethod, aspect_id, method, serialized.len());
+		}
+		let start = Instant::now();
 		let result = message_sender_handle
 			.method(self.id.0, aspect_id, method, &serialized, fds)
-			.await?
-			.map_err(ServerError::RemoteMethodError)?;
+			.await;
+		if let Some(client) = &client {
+			client
+				.metrics
+				.responded(MessageKind::SentMethod, aspect_id, method, start.elapsed());
+		}
+		let result = result?.map_err(ServerError::RemoteMethodError)?;
 
 		let (message, fds) = result.into_components();
 		let deserialized: D = deserialize(&message, fds)?;
@@ -3,15 +3,17 @@ use super::{Aspect, AspectIdentifier, Node};
 use crate::core::Id;
 use crate::core::client::{CLIENTS, Client};
 use crate::core::client_state::ClientStateParsed;
-use crate::core::error::Result;
+use crate::core::error::{Result, ServerError};
+use crate::core::resource_policy::{canonicalize_within, widens_access};
 use crate::nodes::spatial::SPATIAL_REF_ASPECT_ALIAS_INFO;
 use crate::session::connection_env;
+use directories::BaseDirs;
 use glam::Mat4;
 use stardust_xr_server_foundation::bail;
 use std::path::PathBuf;
 use std::sync::Arc;
-use std::time::Instant;
-use tracing::info;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
 
 stardust_xr_server_codegen::codegen_root_protocol!();
 
@@ -51,6 +53,10 @@ impl Root {
 		// spatial.set_spatial_parent(None).unwrap();
 		spatial.set_local_transform(transform);
 	}
+	/// Let the client know the server is shutting down and how long it has to save and disconnect
+	pub fn send_shutdown(&self, deadline: Duration) -> Result<()> {
+		root_client::shutdown(&self.node, deadline.as_secs_f32())
+	}
 	pub async fn save_state(&self) -> Result<ClientState> {
 		Ok(root_client::save_state(&self.node).await?)
 	}
@@ -63,10 +69,19 @@ impl Aspect for Root {
 }
 impl RootAspect for Root {
 	async fn get_state(_node: Arc<Node>, calling_client: Arc<Client>) -> Result<ClientState> {
-		let Some(state) = calling_client.state.get() else {
+		let Some(state) = calling_client.state.lock().clone() else {
 			bail!("Couldn't get state");
 		};
-		Ok(state.clone())
+		Ok(state)
+	}
+
+	#[doc = "Present the startup token this client was launched with and get the state saved under it.\n\n Use this when the `STARDUST_STARTUP_TOKEN` environment variable might not be visible in `/proc/{pid}/environ`, e.g. inside a sandbox or behind a wrapper that re-execs.\n"]
+	async fn present_state_token(
+		_node: Arc<Node>,
+		calling_client: Arc<Client>,
+		token: String,
+	) -> Result<ClientState> {
+		calling_client.restore_state(&token)
 	}
 
 	#[doc = "Get a hashmap of all the environment variables to connect a given app to the stardust server"]
@@ -77,7 +92,7 @@ impl RootAspect for Root {
 		Ok(connection_env())
 	}
 
-	#[doc = "Generate a client state token and return it back.\n\n When launching a new client, set the environment variable `STARDUST_STARTUP_TOKEN` to the returned string.\n Make sure the environment variable shows in `/proc/{pid}/environ` as that's the only reliable way to pass the value to the server (suggestions welcome).\n"]
+	#[doc = "Generate a client state token and return it back.\n\n When launching a new client, set the environment variable `STARDUST_STARTUP_TOKEN` to the returned string.\n The server reads it from `/proc/{pid}/environ` if it can, otherwise the client should pass it to `present_state_token`.\n"]
 	async fn generate_state_token(
 		_node: Arc<Node>,
 		calling_client: Arc<Client>,
@@ -93,11 +108,45 @@ impl RootAspect for Root {
 		prefixes: Vec<String>,
 	) -> Result<()> {
 		info!(?calling_client, ?prefixes, "Set base prefixes");
-		*calling_client.base_resource_prefixes.lock() =
-			prefixes.into_iter().map(PathBuf::from).collect();
+		let allowed_dirs = calling_client.allowed_resource_dirs();
+		let home = BaseDirs::new().map(|dirs| dirs.home_dir().to_path_buf());
+		let mut base_prefixes = Vec::new();
+		for prefix in prefixes.into_iter().map(PathBuf::from) {
+			// canonical so the client can't point a symlink somewhere else once it's been checked
+			let Ok(canonical_prefix) = prefix.canonicalize() else {
+				warn!(?calling_client, prefix = ?prefix.display(), "Base prefix doesn't exist");
+				continue;
+			};
+			// files in base prefixes may be loaded, so a client can't be allowed to make its
+			// whole home directory one
+			if allowed_dirs.as_ref().is_some_and(|allowed_dirs| {
+				canonicalize_within(&canonical_prefix, allowed_dirs).is_none()
+			}) && widens_access(&canonical_prefix, home.as_deref())
+			{
+				return Err(ServerError::ResourceNotAllowed(prefix));
+			}
+			base_prefixes.push(canonical_prefix);
+		}
+		*calling_client.base_resource_prefixes.lock() = base_prefixes;
 		Ok(())
 	}
 
+	#[doc = "Signal that this client is ready for the server to shut down after receiving `shutdown`. There is no reply; disconnecting does the same."]
+	fn acknowledge_shutdown(_node: Arc<Node>, calling_client: Arc<Client>) -> Result<()> {
+		calling_client.acknowledge_shutdown();
+		Ok(())
+	}
+
+	#[doc = "Hold back every signal this client sends from now on until `commit_transaction`, so they all take effect in the same frame. Method calls are still handled right away."]
+	fn begin_transaction(_node: Arc<Node>, calling_client: Arc<Client>) -> Result<()> {
+		calling_client.transaction.begin()
+	}
+
+	#[doc = "Apply every signal since `begin_transaction` at once before the next frame."]
+	fn commit_transaction(_node: Arc<Node>, calling_client: Arc<Client>) -> Result<()> {
+		calling_client.transaction.commit()
+	}
+
 	#[doc = "Cleanly disconnect from the server"]
 	fn disconnect(_node: Arc<Node>, calling_client: Arc<Client>) -> Result<()> {
 		calling_client.disconnect(Ok(()));
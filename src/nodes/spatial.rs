@@ -1,9 +1,13 @@
 use super::alias::Alias;
+use super::export::{ExportRegistry, ExportRules};
+use super::transform_subscription::{self, TRANSFORMS_DIRTY, Thresholds, send_transform_updates};
+use super::tween::{TWEENING_SPATIALS, Tween, update_tweens};
 use super::{Aspect, AspectIdentifier};
 use crate::bevy_int::entity_handle::EntityHandle;
 use crate::core::Id;
-use crate::core::client::Client;
-use crate::core::error::Result;
+use crate::core::client::{CLIENTS, Client};
+use crate::core::error::{Result, ServerError};
+use crate::core::permissions::Permission;
 use crate::core::registry::Registry;
 use crate::nodes::{Node, OWNED_ASPECT_ALIAS_INFO};
 use bevy::ecs::entity::EntityHashMap;
@@ -11,15 +15,16 @@ use bevy::prelude::Transform as BevyTransform;
 use bevy::prelude::*;
 use bevy::render::primitives::Aabb;
 use color_eyre::eyre::OptionExt;
-use dashmap::DashMap;
 use glam::{Mat4, Quat, Vec3};
 use mint::Vector3;
 use parking_lot::{Mutex, RwLock};
 use stardust_xr_server_foundation::bail;
+use std::collections::VecDeque;
 use std::fmt::Debug;
 use std::pin::Pin;
-use std::sync::atomic::Ordering;
-use std::sync::{Arc, LazyLock, OnceLock, Weak};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock, Weak};
+use std::time::{Duration, Instant};
 use std::{f32, ptr};
 
 pub struct SpatialNodePlugin;
@@ -27,7 +32,12 @@ impl Plugin for SpatialNodePlugin {
 	fn build(&self, app: &mut App) {
 		app.add_systems(
 			PostUpdate,
-			(spawn_spatial_nodes, update_spatial_nodes)
+			(
+				spawn_spatial_nodes,
+				update_tweens,
+				update_spatial_nodes,
+				send_transform_updates,
+			)
 				.chain()
 				.before(TransformSystem::TransformPropagate),
 		);
@@ -82,6 +92,44 @@ pub struct SpatialNode(pub Weak<Spatial>);
 const EPSILON: f32 = 0.00001;
 
 stardust_xr_server_codegen::codegen_spatial_protocol!();
+impl TryFrom<ExportOptions> for ExportRules {
+	type Error = ServerError;
+
+	fn try_from(options: ExportOptions) -> Result<Self> {
+		let expires_at = match options.expiry {
+			Some(expiry) => Some(
+				Duration::try_from_secs_f32(expiry.max(0.0))
+					.ok()
+					.and_then(|expiry| Instant::now().checked_add(expiry))
+					.ok_or_eyre("Export expiry is out of range")?,
+			),
+			None => None,
+		};
+		// pids get reused, so pin the export to the client connected with it right now
+		let target_client = match options.target_pid {
+			Some(target_pid) => {
+				let mut clients = CLIENTS
+					.get_vec()
+					.into_iter()
+					.filter(|client| client.pid == Some(target_pid));
+				let Some(client) = clients.next() else {
+					bail!("No client with pid {target_pid} is connected");
+				};
+				if clients.next().is_some() {
+					bail!("More than one client with pid {target_pid} is connected");
+				}
+				Some(client.id)
+			}
+			None => None,
+		};
+		Ok(ExportRules {
+			one_shot: options.one_shot,
+			expires_at,
+			target_client,
+		})
+	}
+}
+
 impl Transform {
 	pub fn to_mat4(&self, position: bool, rotation: bool, scale: bool) -> Mat4 {
 		let position = position
@@ -93,30 +141,56 @@ impl Transform {
 			.flatten()
 			.unwrap_or_else(|| Quat::IDENTITY.into());
 
-		// Zero scale values break everything
 		let scale = scale
 			.then_some(self.scale)
 			.flatten()
-			.map(|s| Vector3 {
-				x: if s.x == 0.0 { EPSILON } else { s.x },
-				y: if s.y == 0.0 { EPSILON } else { s.y },
-				z: if s.z == 0.0 { EPSILON } else { s.z },
-			})
-			.unwrap_or_else(|| Vector3::from([1.0; 3]));
+			.map_or(Vec3::ONE, |s| nonzero_scale(s.into()));
 
-		Mat4::from_scale_rotation_translation(scale.into(), rotation.into(), position.into())
+		Mat4::from_scale_rotation_translation(scale, rotation.into(), position.into())
 	}
 }
+/// Zero scale values break everything
+pub(super) fn nonzero_scale(scale: Vec3) -> Vec3 {
+	Vec3::select(scale.cmpeq(Vec3::ZERO), Vec3::splat(EPSILON), scale)
+}
 
-// uses dashmap because it's concurrent and doesn't use fxhash because exported
-pub static EXPORTED_SPATIALS: LazyLock<DashMap<u64, Weak<Node>>> = LazyLock::new(DashMap::new);
+pub static EXPORTED_SPATIALS: ExportRegistry = ExportRegistry::new();
+
+/// The box around all 8 corners of `aabb` once transformed, so rotations can't cut off parts
+/// of it like transforming just the min and max would
+pub fn transform_aabb(aabb: Aabb, transform: Mat4) -> Aabb {
+	let (min, max) = (Vec3::from(aabb.min()), Vec3::from(aabb.max()));
+	Aabb::enclosing((0..8).map(|corner| {
+		transform.transform_point3(Vec3::new(
+			if corner & 1 == 0 { min.x } else { max.x },
+			if corner & 2 == 0 { min.y } else { max.y },
+			if corner & 4 == 0 { min.z } else { max.z },
+		))
+	}))
+	.unwrap()
+}
+/// The box around all of `aabbs`, `None` if there are none
+pub fn merge_aabbs(aabbs: impl IntoIterator<Item = Aabb>) -> Option<Aabb> {
+	Aabb::enclosing(
+		aabbs
+			.into_iter()
+			.flat_map(|aabb| [Vec3::from(aabb.min()), Vec3::from(aabb.max())]),
+	)
+}
 
 pub struct Spatial {
 	pub node: Weak<Node>,
 	entity: RwLock<Option<EntityHandle>>,
 	parent: RwLock<Option<Arc<Spatial>>>,
 	transform: RwLock<Mat4>,
+	/// `None` when the local transform of this spatial or any of its ancestors changed since it
+	/// was last calculated
+	global_transform: Mutex<Option<Mat4>>,
+	/// Bumped before `global_transform` gets cleared, so a global transform calculated while it
+	/// changed never gets cached
+	transform_generation: AtomicU64,
 	children: Registry<Spatial>,
+	tweens: Mutex<VecDeque<Tween>>,
 	pub bounding_box_calc:
 		OnceLock<for<'a> fn(&'a Node) -> Pin<Box<dyn Future<Output = Aabb> + 'a + Send + Sync>>>,
 }
@@ -128,7 +202,10 @@ impl Spatial {
 			entity: RwLock::new(None),
 			parent: RwLock::new(parent),
 			transform: RwLock::new(transform),
+			global_transform: Mutex::new(None),
+			transform_generation: AtomicU64::new(0),
 			children: Registry::new(),
+			tweens: Mutex::new(VecDeque::new()),
 			bounding_box_calc: OnceLock::default(),
 		});
 		spatial.mark_dirty();
@@ -174,19 +251,14 @@ impl Spatial {
 			None => Aabb::default(),
 		};
 		for child in self.children.get_valid_contents() {
-			let mat = child.local_transform();
 			let child_aabb = Box::pin(child.get_bounding_box()).await;
-			bounds = Aabb::enclosing([
-				bounds.min().into(),
-				bounds.max().into(),
-				mat.transform_point3(child_aabb.min().into()),
-				mat.transform_point3(child_aabb.max().into()),
-			])
-			.unwrap();
+			bounds =
+				merge_aabbs([bounds, transform_aabb(child_aabb, child.local_transform())]).unwrap();
 		}
 		bounds
 	}
 	pub(super) fn mark_dirty(&self) {
+		TRANSFORMS_DIRTY.store(true, Ordering::Relaxed);
 		let Some(entity) = self.entity.read().as_ref().map(|v| v.get()) else {
 			return;
 		};
@@ -203,6 +275,49 @@ impl Spatial {
 			.insert(entity, (transform, parent));
 	}
 
+	/// Animate the local transform, after the tweens already running if `queue` is set and
+	/// instead of them otherwise
+	pub fn tween(self: &Arc<Self>, tween: Tween, queue: bool) {
+		if !queue {
+			self.cancel_tweens();
+		}
+		let mut tweens = self.tweens.lock();
+		tweens.push_back(tween);
+		// under the lock so `step_tweens` can't unregister the spatial right after this
+		TWEENING_SPATIALS.add_raw(self);
+	}
+	/// Stop every tween, leaving the spatial wherever it got to
+	pub fn cancel_tweens(&self) {
+		let cancelled = std::mem::take(&mut *self.tweens.lock());
+		for _ in cancelled {
+			self.send_tween_finished(true);
+		}
+	}
+	/// Run the current tween for a frame, unregistering the spatial once there are none left
+	pub(super) fn step_tweens(&self, delta: f32) {
+		let current = self.local_transform();
+		let mut tweens = self.tweens.lock();
+		let Some(tween) = tweens.front_mut() else {
+			TWEENING_SPATIALS.remove(self);
+			return;
+		};
+		let (transform, done) = tween.step(current, delta);
+		if done {
+			tweens.pop_front();
+		}
+		// setting the transform and messaging the client don't need the tweens locked
+		drop(tweens);
+		self.set_local_transform(transform);
+		if done {
+			self.send_tween_finished(false);
+		}
+	}
+	fn send_tween_finished(&self, cancelled: bool) {
+		if let Some(node) = self.node() {
+			let _ = spatial_client::tween_finished(&node, cancelled);
+		}
+	}
+
 	pub fn local_transform(&self) -> Mat4 {
 		*self.transform.read()
 	}
@@ -229,15 +344,45 @@ impl Spatial {
 		self.local_visible()
 	}
 	pub fn global_transform(&self) -> Mat4 {
-		let parent_transform = self
-			.get_parent()
-			.as_deref()
-			.map(Self::global_transform)
-			.unwrap_or_default();
-		parent_transform * self.local_transform()
+		self.cache_global_transform().0
+	}
+	/// The global transform and whether it's cached now, a child may only cache its own global
+	/// transform if its parent's is
+	fn cache_global_transform(&self) -> (Mat4, bool) {
+		if let Some(global_transform) = *self.global_transform.lock() {
+			return (global_transform, true);
+		}
+		let generation = self.transform_generation.load(Ordering::SeqCst);
+		let (parent_transform, parent_cached) =
+			self.get_parent().map_or((Mat4::IDENTITY, true), |parent| {
+				parent.cache_global_transform()
+			});
+		let global_transform = parent_transform * self.local_transform();
+		let mut cached = self.global_transform.lock();
+		// something in the hierarchy changed while calculating, so this might already be stale
+		let cacheable =
+			parent_cached && self.transform_generation.load(Ordering::SeqCst) == generation;
+		if cacheable {
+			cached.replace(global_transform);
+		}
+		(global_transform, cacheable)
+	}
+	fn invalidate_global_transform(&self) {
+		// before clearing, so a calculation that already checked the generation must have cached
+		// its result by the time it gets cleared
+		self.transform_generation.fetch_add(1, Ordering::SeqCst);
+		// a child's global transform only gets cached after its parent's, so if this one isn't
+		// cached none of the children's are either
+		if self.global_transform.lock().take().is_none() {
+			return;
+		}
+		for child in self.children.get_valid_contents() {
+			child.invalidate_global_transform();
+		}
 	}
 	pub fn set_local_transform(&self, transform: Mat4) {
 		*self.transform.write() = transform;
+		self.invalidate_global_transform();
 		self.mark_dirty();
 	}
 	pub fn set_local_transform_components(
@@ -296,7 +441,7 @@ impl Spatial {
 		}
 	}
 
-	fn get_parent(&self) -> Option<Arc<Spatial>> {
+	pub fn get_parent(&self) -> Option<Arc<Spatial>> {
 		self.parent.read().clone()
 	}
 	fn set_parent(self: &Arc<Self>, new_parent: &Arc<Spatial>) {
@@ -306,6 +451,7 @@ impl Spatial {
 		new_parent.children.add_raw(self);
 
 		*self.parent.write() = Some(new_parent.clone());
+		self.invalidate_global_transform();
 		self.mark_dirty();
 	}
 
@@ -383,11 +529,37 @@ impl SpatialAspect for Spatial {
 		Ok(())
 	}
 
-	// legit gotta find a way to remove old ones, this just keeps the node alive
+	#[doc = "Animate the local transform to `transform` over `duration` seconds. If `queue` is set the tween starts once the ones already running are done, otherwise it replaces them. `tween_finished` is sent for every tween that completes or gets cancelled. While tweening, the tween overrides `set_local_transform`."]
+	fn tween_transform(
+		node: Arc<Node>,
+		_calling_client: Arc<Client>,
+		transform: Transform,
+		duration: f32,
+		easing: Easing,
+		queue: bool,
+	) -> Result<()> {
+		let this_spatial = node.get_aspect::<Spatial>()?;
+		this_spatial.tween(Tween::new(transform, duration, easing), queue);
+		Ok(())
+	}
+
+	#[doc = "Stop all tweens, leaving the spatial where it is."]
+	fn cancel_tweens(node: Arc<Node>, _calling_client: Arc<Client>) -> Result<()> {
+		node.get_aspect::<Spatial>()?.cancel_tweens();
+		Ok(())
+	}
+
 	async fn export_spatial(node: Arc<Node>, _calling_client: Arc<Client>) -> Result<Id> {
-		let id = rand::random();
-		EXPORTED_SPATIALS.insert(id, Arc::downgrade(&node));
-		Ok(id.into())
+		Ok(EXPORTED_SPATIALS.export(&node, ExportRules::default()))
+	}
+
+	#[doc = "Like `export_spatial`, but the UID can be limited to one import, a target client and a lifetime"]
+	async fn export_spatial_with_options(
+		node: Arc<Node>,
+		_calling_client: Arc<Client>,
+		options: ExportOptions,
+	) -> Result<Id> {
+		Ok(EXPORTED_SPATIALS.export(&node, options.try_into()?))
 	}
 }
 impl PartialEq for Spatial {
@@ -406,6 +578,7 @@ impl Debug for Spatial {
 impl Drop for Spatial {
 	fn drop(&mut self) {
 		SPATIAL_REGISTRY.remove(self);
+		TWEENING_SPATIALS.remove(self);
 	}
 }
 
@@ -438,12 +611,7 @@ impl SpatialRefAspect for SpatialRef {
 		let this_spatial = node.get_aspect::<Spatial>()?;
 		let relative_spatial = relative_to.get_aspect::<Spatial>()?;
 		let mat = Spatial::space_to_space_matrix(Some(&this_spatial), Some(&relative_spatial));
-		let bb = this_spatial.get_bounding_box().await;
-		let bounds = Aabb::enclosing([
-			mat.transform_point3(bb.min().into()),
-			mat.transform_point3(bb.max().into()),
-		])
-		.unwrap();
+		let bounds = transform_aabb(this_spatial.get_bounding_box().await, mat);
 
 		Ok(BoundingBox {
 			center: Vec3::from(bounds.center).into(),
@@ -451,6 +619,54 @@ impl SpatialRefAspect for SpatialRef {
 		})
 	}
 
+	#[doc = "Get `transform_changed` whenever the transform relative to `relative_to` changes by more than a threshold, at most once per frame. The current transform is sent right away. Subscribing again replaces the previous subscription."]
+	fn subscribe_transform(
+		node: Arc<Node>,
+		calling_client: Arc<Client>,
+		relative_to: Arc<Node>,
+		translation_threshold: f32,
+		rotation_threshold: f32,
+		scale_threshold: f32,
+	) -> Result<()> {
+		let relative_spatial = relative_to.get_aspect::<Spatial>()?;
+		transform_subscription::subscribe(
+			&node,
+			&calling_client,
+			&relative_spatial,
+			Thresholds {
+				translation: translation_threshold,
+				rotation: rotation_threshold,
+				scale: scale_threshold,
+			},
+		)
+	}
+
+	#[doc = "Stop getting `transform_changed`."]
+	fn unsubscribe_transform(node: Arc<Node>, calling_client: Arc<Client>) -> Result<()> {
+		transform_subscription::unsubscribe(&node, &calling_client);
+		Ok(())
+	}
+
+	#[doc = "Get the parent of this spatial, if there is one and this client may see it."]
+	async fn get_parent(node: Arc<Node>, calling_client: Arc<Client>) -> Result<Option<Id>> {
+		let this_spatial = node.get_aspect::<Spatial>()?;
+		let Some(parent) = this_spatial.get_parent() else {
+			return Ok(None);
+		};
+		spatial_ref_id(&parent, &calling_client)
+	}
+
+	#[doc = "Get the children of this spatial that this client may see."]
+	async fn get_children(node: Arc<Node>, calling_client: Arc<Client>) -> Result<Vec<Id>> {
+		let this_spatial = node.get_aspect::<Spatial>()?;
+		this_spatial
+			.children
+			.get_valid_contents()
+			.iter()
+			.filter_map(|child| spatial_ref_id(child, &calling_client).transpose())
+			.collect()
+	}
+
 	async fn get_transform(
 		node: Arc<Node>,
 		_calling_client: Arc<Client>,
@@ -473,6 +689,64 @@ impl SpatialRefAspect for SpatialRef {
 	}
 }
 
+/// The ID `client` knows `spatial` by, aliasing it as a spatial ref if it's another client's.
+/// Other clients' spatials are hidden without the import permission, or if they're outside of
+/// every subtree the client was given, since walking the hierarchy would otherwise get around
+/// both. The aliases count against the client's alias quota like any other.
+fn spatial_ref_id(spatial: &Spatial, client: &Arc<Client>) -> Result<Option<Id>> {
+	let Some(node) = spatial.node() else {
+		return Ok(None);
+	};
+	if node.get_client().is_some_and(|owner| owner.id == client.id) {
+		return Ok(Some(node.get_id()));
+	}
+	if !client.has_permission(Permission::Import) || !reachable(spatial, client) {
+		return Ok(None);
+	}
+	if let Some(alias) = client
+		.spatial_ref_aliases
+		.get_from_original_node(Arc::downgrade(&node))
+	{
+		return Ok(Some(alias.get_id()));
+	}
+	let alias = Alias::create(
+		&node,
+		client,
+		SPATIAL_REF_ASPECT_ALIAS_INFO.clone(),
+		Some(&client.spatial_ref_aliases),
+	)?;
+	Ok(Some(alias.get_id()))
+}
+
+/// Whether `spatial` is or is below a spatial that `client` owns or was handed an alias of, so
+/// walking up from an imported spatial stops at it
+fn reachable(spatial: &Spatial, client: &Client) -> bool {
+	let given = |spatial: &Spatial| {
+		let Some(node) = spatial.node() else {
+			return false;
+		};
+		node.get_client().is_some_and(|owner| owner.id == client.id)
+			|| node.aliases.get_valid_contents().iter().any(|alias| {
+				alias.node.upgrade().is_some_and(|alias| {
+					alias.get_client().is_some_and(|owner| owner.id == client.id)
+						// otherwise every step up would make the next one reachable
+						&& !client.spatial_ref_aliases.contains(&alias)
+				})
+			})
+	};
+	if given(spatial) {
+		return true;
+	}
+	let mut ancestor = spatial.get_parent();
+	while let Some(spatial) = ancestor {
+		if given(&spatial) {
+			return true;
+		}
+		ancestor = spatial.get_parent();
+	}
+	false
+}
+
 impl InterfaceAspect for Interface {
 	fn create_spatial(
 		_node: Arc<Node>,
@@ -494,8 +768,7 @@ impl InterfaceAspect for Interface {
 		uid: Id,
 	) -> Result<Id> {
 		let node = EXPORTED_SPATIALS
-			.get(&uid.0)
-			.and_then(|s| s.upgrade())
+			.import(uid, &calling_client)
 			.map(|s| {
 				Alias::create(
 					&s,
@@ -508,4 +781,304 @@ impl InterfaceAspect for Interface {
 			.ok_or_eyre("Couldn't find spatial with that ID")?;
 		Ok(node.get_id())
 	}
+
+	#[doc = "Stop a UID from `export_spatial` from being imported. Only the client that exported it can revoke it."]
+	fn revoke_spatial_export(_node: Arc<Node>, calling_client: Arc<Client>, uid: Id) -> Result<()> {
+		if !EXPORTED_SPATIALS.revoke(uid, &calling_client) {
+			bail!("Couldn't find an export with that ID that this client can revoke");
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::core::test_client::{ReceivedSignal, TestClient};
+	use crate::nodes::{OWNED_DESTROY_SERVER_OPCODE, Owned};
+
+	fn translation(translation: [f32; 3]) -> Transform {
+		Transform {
+			translation: Some(translation.into()),
+			rotation: None,
+			scale: None,
+		}
+	}
+
+	#[tokio::test]
+	async fn spatial_transforms() {
+		let client = TestClient::connect().await;
+		let parent = client.new_id();
+		let child = client.new_id();
+		client.signal(
+			INTERFACE_NODE_ID,
+			Interface::ID,
+			INTERFACE_CREATE_SPATIAL_SERVER_OPCODE,
+			(parent, Id(0), translation([0.0, 1.0, 0.0])),
+		);
+		client.signal(
+			INTERFACE_NODE_ID,
+			Interface::ID,
+			INTERFACE_CREATE_SPATIAL_SERVER_OPCODE,
+			(child, parent, translation([1.0, 0.0, 0.0])),
+		);
+		client.sync().await;
+
+		let child_spatial = client
+			.client
+			.scenegraph
+			.get_node(child)
+			.unwrap()
+			.get_aspect::<Spatial>()
+			.unwrap();
+		assert!(
+			child_spatial
+				.global_transform()
+				.transform_point3(Vec3::ZERO)
+				.abs_diff_eq(Vec3::new(1.0, 1.0, 0.0), EPSILON)
+		);
+
+		// moving the parent moves the child with it
+		client.signal(
+			parent,
+			Spatial::ID,
+			SPATIAL_SET_LOCAL_TRANSFORM_SERVER_OPCODE,
+			translation([0.0, 2.0, 0.0]),
+		);
+		let transform: Transform = client
+			.method(
+				child,
+				SpatialRef::ID,
+				SPATIAL_REF_GET_TRANSFORM_SERVER_OPCODE,
+				Id(0),
+			)
+			.await
+			.unwrap();
+		let position = Vec3::from(transform.translation.unwrap());
+		assert!(position.abs_diff_eq(Vec3::new(1.0, 2.0, 0.0), EPSILON));
+	}
+
+	#[tokio::test]
+	async fn destroyed_spatial_is_removed() {
+		let client = TestClient::connect().await;
+		let spatial = client.new_id();
+		client.signal(
+			INTERFACE_NODE_ID,
+			Interface::ID,
+			INTERFACE_CREATE_SPATIAL_SERVER_OPCODE,
+			(spatial, Id(0), translation([0.0; 3])),
+		);
+		client.sync().await;
+		assert!(client.client.scenegraph.get_node(spatial).is_some());
+
+		client.signal(spatial, Owned::ID, OWNED_DESTROY_SERVER_OPCODE, ());
+		client.sync().await;
+		assert!(client.client.scenegraph.get_node(spatial).is_none());
+	}
+
+	#[tokio::test]
+	async fn import_needs_permission() {
+		let exporter = TestClient::connect().await;
+		let importer = TestClient::connect().await;
+		let spatial = exporter.new_id();
+		exporter.signal(
+			INTERFACE_NODE_ID,
+			Interface::ID,
+			INTERFACE_CREATE_SPATIAL_SERVER_OPCODE,
+			(spatial, Id(0), translation([0.0; 3])),
+		);
+		exporter.sync().await;
+		let node = exporter.client.scenegraph.get_node(spatial).unwrap();
+		let uid = EXPORTED_SPATIALS.export(&node, ExportRules::default());
+
+		importer
+			.client
+			.permissions
+			.lock()
+			.remove(&Permission::Import);
+		let error = importer
+			.method::<Id>(
+				INTERFACE_NODE_ID,
+				Interface::ID,
+				INTERFACE_IMPORT_SPATIAL_REF_SERVER_OPCODE,
+				uid,
+			)
+			.await
+			.unwrap_err();
+		assert!(error.contains("import permission"), "{error}");
+
+		importer
+			.client
+			.permissions
+			.lock()
+			.insert(Permission::Import);
+		importer
+			.method::<Id>(
+				INTERFACE_NODE_ID,
+				Interface::ID,
+				INTERFACE_IMPORT_SPATIAL_REF_SERVER_OPCODE,
+				uid,
+			)
+			.await
+			.unwrap();
+	}
+
+	#[tokio::test]
+	async fn hierarchy_queries() {
+		let owner = TestClient::connect().await;
+		let other = TestClient::connect().await;
+		let parent = owner.new_id();
+		let child = owner.new_id();
+		owner.signal(
+			INTERFACE_NODE_ID,
+			Interface::ID,
+			INTERFACE_CREATE_SPATIAL_SERVER_OPCODE,
+			(parent, Id(0), translation([0.0; 3])),
+		);
+		owner.signal(
+			INTERFACE_NODE_ID,
+			Interface::ID,
+			INTERFACE_CREATE_SPATIAL_SERVER_OPCODE,
+			(child, parent, translation([0.0; 3])),
+		);
+		owner.sync().await;
+		let children = owner
+			.method::<Vec<Id>>(
+				parent,
+				SpatialRef::ID,
+				SPATIAL_REF_GET_CHILDREN_SERVER_OPCODE,
+				(),
+			)
+			.await
+			.unwrap();
+		assert_eq!(children, vec![child]);
+		let child_parent = owner
+			.method::<Option<Id>>(
+				child,
+				SpatialRef::ID,
+				SPATIAL_REF_GET_PARENT_SERVER_OPCODE,
+				(),
+			)
+			.await
+			.unwrap();
+		assert_eq!(child_parent, Some(parent));
+
+		let node = owner.client.scenegraph.get_node(parent).unwrap();
+		let uid = EXPORTED_SPATIALS.export(&node, ExportRules::default());
+		let imported = other
+			.method::<Id>(
+				INTERFACE_NODE_ID,
+				Interface::ID,
+				INTERFACE_IMPORT_SPATIAL_REF_SERVER_OPCODE,
+				uid,
+			)
+			.await
+			.unwrap();
+		let get_children = || {
+			other.method::<Vec<Id>>(
+				imported,
+				SpatialRef::ID,
+				SPATIAL_REF_GET_CHILDREN_SERVER_OPCODE,
+				(),
+			)
+		};
+		let aliases = get_children().await.unwrap();
+		assert_eq!(aliases.len(), 1);
+		assert_ne!(aliases[0], child);
+		// the same alias is handed out again
+		assert_eq!(get_children().await.unwrap(), aliases);
+		let get_parent = |node| {
+			other.method::<Option<Id>>(
+				node,
+				SpatialRef::ID,
+				SPATIAL_REF_GET_PARENT_SERVER_OPCODE,
+				(),
+			)
+		};
+		let imported_parent = get_parent(aliases[0]).await.unwrap().unwrap();
+		// walking can't climb out of the imported subtree to the owner's root
+		assert_eq!(get_parent(imported).await.unwrap(), None);
+		assert_eq!(get_parent(imported_parent).await.unwrap(), None);
+
+		other.client.permissions.lock().remove(&Permission::Import);
+		assert!(get_children().await.unwrap().is_empty());
+	}
+
+	#[tokio::test]
+	async fn transform_subscription() {
+		let client = TestClient::connect().await;
+		let spatial = client.new_id();
+		client.signal(
+			INTERFACE_NODE_ID,
+			Interface::ID,
+			INTERFACE_CREATE_SPATIAL_SERVER_OPCODE,
+			(spatial, Id(0), translation([1.0, 0.0, 0.0])),
+		);
+		client.signal(
+			spatial,
+			SpatialRef::ID,
+			SPATIAL_REF_SUBSCRIBE_TRANSFORM_SERVER_OPCODE,
+			(Id(0), 0.1_f32, 0.1_f32, 0.1_f32),
+		);
+		client.sync().await;
+		let is_transform_changed = |signal: &ReceivedSignal| {
+			signal.node == spatial.0 && signal.method == SPATIAL_REF_TRANSFORM_CHANGED_CLIENT_OPCODE
+		};
+
+		send_transform_updates();
+		let transform: Transform = client.wait_for_signal(is_transform_changed).await.args();
+		assert!(Vec3::from(transform.translation.unwrap()).abs_diff_eq(Vec3::X, EPSILON));
+
+		client.signal(
+			spatial,
+			Spatial::ID,
+			SPATIAL_SET_LOCAL_TRANSFORM_SERVER_OPCODE,
+			translation([2.0, 0.0, 0.0]),
+		);
+		client.sync().await;
+		send_transform_updates();
+		let transform: Transform = client.wait_for_signal(is_transform_changed).await.args();
+		assert!(Vec3::from(transform.translation.unwrap()).abs_diff_eq(Vec3::X * 2.0, EPSILON));
+	}
+
+	#[test]
+	fn cached_global_transform() {
+		let root = Spatial::new(Weak::new(), None, Mat4::from_translation(Vec3::X));
+		let parent = Spatial::new(Weak::new(), None, Mat4::from_translation(Vec3::Y));
+		let child = Spatial::new(Weak::new(), None, Mat4::from_translation(Vec3::Z));
+		parent.set_spatial_parent(&root).unwrap();
+		child.set_spatial_parent(&parent).unwrap();
+		let position = |spatial: &Spatial| spatial.global_transform().w_axis.truncate();
+		assert!(position(&child).abs_diff_eq(Vec3::ONE, EPSILON));
+
+		// changing an ancestor invalidates the whole subtree
+		root.set_local_transform(Mat4::IDENTITY);
+		assert!(position(&child).abs_diff_eq(Vec3::new(0.0, 1.0, 1.0), EPSILON));
+
+		child.set_spatial_parent(&root).unwrap();
+		assert!(position(&child).abs_diff_eq(Vec3::Z, EPSILON));
+		parent.set_local_transform(Mat4::IDENTITY);
+		assert!(position(&child).abs_diff_eq(Vec3::Z, EPSILON));
+	}
+
+	#[test]
+	fn rotated_bounding_box() {
+		let aabb = Aabb::from_min_max(Vec3::ZERO, Vec3::new(1.0, 1.0, 0.0));
+		let rotated = transform_aabb(aabb, Mat4::from_rotation_z(f32::consts::FRAC_PI_4));
+		// transforming just the min and max would end up with zero width here
+		let half_diagonal = f32::consts::FRAC_1_SQRT_2;
+		assert!(Vec3::from(rotated.min()).abs_diff_eq(Vec3::X * -half_diagonal, 0.0001));
+		assert!(
+			Vec3::from(rotated.max())
+				.abs_diff_eq(Vec3::new(half_diagonal, 2.0 * half_diagonal, 0.0), 0.0001)
+		);
+
+		let merged = merge_aabbs([
+			aabb,
+			transform_aabb(aabb, Mat4::from_translation(Vec3::Y * 3.0)),
+		])
+		.unwrap();
+		assert!(Vec3::from(merged.max()).abs_diff_eq(Vec3::new(1.0, 4.0, 0.0), 0.0001));
+		assert!(merge_aabbs([]).is_none());
+	}
 }
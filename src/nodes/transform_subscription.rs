@@ -0,0 +1,167 @@
+use super::{
+	AspectIdentifier, Node,
+	spatial::{SPATIAL_REF_TRANSFORM_CHANGED_CLIENT_OPCODE, Spatial, SpatialRef, Transform},
+};
+use crate::core::{client::Client, error::Result};
+use glam::Mat4;
+use parking_lot::Mutex;
+use stardust_xr_server_foundation::bail;
+use stardust_xr_wire::flex::serialize;
+use std::sync::{
+	Arc, Weak,
+	atomic::{AtomicBool, Ordering},
+};
+
+/// Set by `Spatial::mark_dirty`, so the subscriptions are only checked on frames where
+/// something moved
+pub(super) static TRANSFORMS_DIRTY: AtomicBool = AtomicBool::new(false);
+static SUBSCRIPTIONS: Mutex<Vec<TransformSubscription>> = Mutex::new(Vec::new());
+
+/// How far a spatial has to move relative to the reference space before it's sent again
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Thresholds {
+	/// Meters
+	pub translation: f32,
+	/// Radians
+	pub rotation: f32,
+	/// Largest change of the scale on any axis
+	pub scale: f32,
+}
+impl Thresholds {
+	fn exceeded(&self, last: Mat4, current: Mat4) -> bool {
+		let (last_scale, last_rotation, last_translation) = last.to_scale_rotation_translation();
+		let (scale, rotation, translation) = current.to_scale_rotation_translation();
+		last_translation.distance(translation) > self.translation
+			|| last_rotation.angle_between(rotation) > self.rotation
+			|| (last_scale - scale).abs().max_element() > self.scale
+	}
+}
+
+struct TransformSubscription {
+	/// The subscribing client's own node for the spatial, so only that client gets the updates
+	subscriber: Weak<Node>,
+	spatial: Weak<Spatial>,
+	relative_to: Weak<Spatial>,
+	thresholds: Thresholds,
+	last_sent: Option<Mat4>,
+}
+impl TransformSubscription {
+	/// Send the transform if it changed enough, returning whether the subscription is still alive
+	fn update(&mut self) -> bool {
+		let (Some(subscriber), Some(spatial), Some(relative_to)) = (
+			self.subscriber.upgrade(),
+			self.spatial.upgrade(),
+			self.relative_to.upgrade(),
+		) else {
+			return false;
+		};
+		let transform = Spatial::space_to_space_matrix(Some(&spatial), Some(&relative_to));
+		if self
+			.last_sent
+			.is_some_and(|last_sent| !self.thresholds.exceeded(last_sent, transform))
+		{
+			return true;
+		}
+		self.last_sent = Some(transform);
+
+		let (scale, rotation, translation) = transform.to_scale_rotation_translation();
+		let Ok(message) = serialize(Transform {
+			translation: Some(translation.into()),
+			rotation: Some(rotation.into()),
+			scale: Some(scale.into()),
+		}) else {
+			return true;
+		};
+		let _ = subscriber.send_remote_signal_to_owner(
+			SpatialRef::ID,
+			SPATIAL_REF_TRANSFORM_CHANGED_CLIENT_OPCODE,
+			message,
+		);
+		true
+	}
+}
+
+/// The node `client` knows `node` by, either the node itself or the client's alias of it
+fn subscriber_node(node: &Arc<Node>, client: &Client) -> Option<Arc<Node>> {
+	if node.get_client().is_some_and(|owner| owner.id == client.id) {
+		return Some(node.clone());
+	}
+	node.aliases
+		.get_valid_contents()
+		.into_iter()
+		.filter_map(|alias| alias.node.upgrade())
+		.find(|alias| {
+			alias
+				.get_client()
+				.is_some_and(|owner| owner.id == client.id)
+		})
+}
+
+/// Start sending `client` the transform of `node` relative to `relative_to`, replacing any
+/// subscription it already had for `node`
+pub fn subscribe(
+	node: &Arc<Node>,
+	client: &Client,
+	relative_to: &Arc<Spatial>,
+	thresholds: Thresholds,
+) -> Result<()> {
+	let spatial = node.get_aspect::<Spatial>()?;
+	let Some(subscriber) = subscriber_node(node, client) else {
+		bail!("Couldn't find this client's node for the spatial");
+	};
+	let subscriber = Arc::downgrade(&subscriber);
+	let mut subscriptions = SUBSCRIPTIONS.lock();
+	subscriptions.retain(|subscription| !subscription.subscriber.ptr_eq(&subscriber));
+	subscriptions.push(TransformSubscription {
+		subscriber,
+		spatial: Arc::downgrade(&spatial),
+		relative_to: Arc::downgrade(relative_to),
+		thresholds,
+		last_sent: None,
+	});
+	// send the first transform on the next frame even if nothing moves
+	TRANSFORMS_DIRTY.store(true, Ordering::Relaxed);
+	Ok(())
+}
+
+pub fn unsubscribe(node: &Arc<Node>, client: &Client) {
+	let Some(subscriber) = subscriber_node(node, client) else {
+		return;
+	};
+	let subscriber = Arc::downgrade(&subscriber);
+	SUBSCRIPTIONS
+		.lock()
+		.retain(|subscription| !subscription.subscriber.ptr_eq(&subscriber));
+}
+
+/// Send every subscription whose transform changed enough, at most once per frame
+pub(super) fn send_transform_updates() {
+	if !TRANSFORMS_DIRTY.swap(false, Ordering::Relaxed) {
+		return;
+	}
+	SUBSCRIPTIONS
+		.lock()
+		.retain_mut(TransformSubscription::update);
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use glam::{Quat, Vec3};
+
+	#[test]
+	fn thresholds() {
+		let thresholds = Thresholds {
+			translation: 0.01,
+			rotation: 0.1,
+			scale: 0.1,
+		};
+		let start = Mat4::IDENTITY;
+		assert!(!thresholds.exceeded(start, start));
+		assert!(!thresholds.exceeded(start, Mat4::from_translation(Vec3::X * 0.005)));
+		assert!(thresholds.exceeded(start, Mat4::from_translation(Vec3::X * 0.02)));
+		assert!(!thresholds.exceeded(start, Mat4::from_quat(Quat::from_rotation_y(0.05))));
+		assert!(thresholds.exceeded(start, Mat4::from_quat(Quat::from_rotation_y(0.2))));
+		assert!(thresholds.exceeded(start, Mat4::from_scale(Vec3::splat(1.5))));
+	}
+}
@@ -0,0 +1,168 @@
+use super::spatial::{Easing, Spatial, Transform, nonzero_scale};
+use crate::core::registry::Registry;
+use bevy::prelude::*;
+use glam::{Mat4, Quat, Vec3};
+use std::f32::consts::PI;
+
+/// Spatials with at least one tween waiting to run
+pub(super) static TWEENING_SPATIALS: Registry<Spatial> = Registry::new();
+
+pub(super) fn update_tweens(time: Res<Time>) {
+	let delta = time.delta_secs();
+	// not retain, stepping can drop the last reference to a spatial, which removes it from the
+	// registry while it's locked
+	for spatial in TWEENING_SPATIALS.get_valid_contents() {
+		spatial.step_tweens(delta);
+	}
+}
+
+impl Easing {
+	/// Map linear progress from 0 to 1 onto the curve
+	pub fn ease(self, t: f32) -> f32 {
+		let t = t.clamp(0.0, 1.0);
+		match self {
+			Easing::Linear => t,
+			Easing::EaseIn => t * t * t,
+			Easing::EaseOut => 1.0 - (1.0 - t).powi(3),
+			Easing::EaseInOut => {
+				if t < 0.5 {
+					4.0 * t * t * t
+				} else {
+					1.0 - (-2.0 * t + 2.0).powi(3) / 2.0
+				}
+			}
+			// overshoots a little before settling
+			Easing::BackOut => {
+				const C1: f32 = 1.70158;
+				const C3: f32 = C1 + 1.0;
+				1.0 + C3 * (t - 1.0).powi(3) + C1 * (t - 1.0).powi(2)
+			}
+			Easing::ElasticOut => {
+				if t == 0.0 || t == 1.0 {
+					t
+				} else {
+					2.0_f32.powf(-10.0 * t) * ((t * 10.0 - 0.75) * (2.0 * PI / 3.0)).sin() + 1.0
+				}
+			}
+		}
+	}
+}
+
+/// An animation of a spatial's local transform towards `target`
+pub struct Tween {
+	target: Transform,
+	duration: f32,
+	easing: Easing,
+	elapsed: f32,
+	/// Scale, rotation and translation when the tween started running, which isn't when it was
+	/// queued
+	start: Option<(Vec3, Quat, Vec3)>,
+}
+impl Tween {
+	pub fn new(target: Transform, duration: f32, easing: Easing) -> Self {
+		Tween {
+			target,
+			duration: duration.max(0.0),
+			easing,
+			elapsed: 0.0,
+			start: None,
+		}
+	}
+
+	/// Move the tween forward by `delta` seconds, returning the new local transform and whether
+	/// the tween is done
+	pub fn step(&mut self, current: Mat4, delta: f32) -> (Mat4, bool) {
+		let (start_scale, start_rotation, start_translation) = *self
+			.start
+			.get_or_insert_with(|| current.to_scale_rotation_translation());
+		self.elapsed += delta;
+		let done = self.elapsed >= self.duration;
+		let t = if done {
+			1.0
+		} else {
+			self.easing.ease(self.elapsed / self.duration)
+		};
+
+		let translation = self.target.translation.map_or(start_translation, |end| {
+			start_translation.lerp(end.into(), t)
+		});
+		// slerp would take the long way around for an overshooting curve
+		let rotation = self.target.rotation.map_or(start_rotation, |end| {
+			start_rotation.lerp(end.into(), t).normalize()
+		});
+		let scale = self
+			.target
+			.scale
+			.map_or(start_scale, |end| start_scale.lerp(end.into(), t));
+		(
+			Mat4::from_scale_rotation_translation(nonzero_scale(scale), rotation, translation),
+			done,
+		)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn easing_endpoints() {
+		for easing in [
+			Easing::Linear,
+			Easing::EaseIn,
+			Easing::EaseOut,
+			Easing::EaseInOut,
+			Easing::BackOut,
+			Easing::ElasticOut,
+		] {
+			assert!(easing.ease(0.0).abs() < 0.0001, "{easing:?}");
+			assert!((easing.ease(1.0) - 1.0).abs() < 0.0001, "{easing:?}");
+		}
+		assert!(Easing::EaseIn.ease(0.5) < 0.5);
+		assert!(Easing::EaseOut.ease(0.5) > 0.5);
+		assert!((Easing::EaseInOut.ease(0.5) - 0.5).abs() < 0.0001);
+	}
+
+	#[test]
+	fn tween_steps() {
+		let mut tween = Tween::new(
+			Transform {
+				translation: Some([2.0, 0.0, 0.0].into()),
+				rotation: None,
+				scale: None,
+			},
+			1.0,
+			Easing::Linear,
+		);
+		let start = Mat4::from_scale(Vec3::splat(2.0));
+
+		let (transform, done) = tween.step(start, 0.5);
+		assert!(!done);
+		let (scale, _, translation) = transform.to_scale_rotation_translation();
+		assert!(translation.abs_diff_eq(Vec3::X, 0.0001));
+		assert!(scale.abs_diff_eq(Vec3::splat(2.0), 0.0001));
+
+		// starts from where it was when the tween began, not where it is now
+		let (transform, done) = tween.step(Mat4::IDENTITY, 0.75);
+		assert!(done);
+		let (scale, _, translation) = transform.to_scale_rotation_translation();
+		assert!(translation.abs_diff_eq(Vec3::X * 2.0, 0.0001));
+		assert!(scale.abs_diff_eq(Vec3::splat(2.0), 0.0001));
+	}
+
+	#[test]
+	fn tween_to_zero_scale() {
+		let mut tween = Tween::new(
+			Transform {
+				translation: None,
+				rotation: None,
+				scale: Some([0.0; 3].into()),
+			},
+			0.0,
+			Easing::Linear,
+		);
+		let (transform, done) = tween.step(Mat4::IDENTITY, 0.0);
+		assert!(done);
+		assert!(transform.inverse().is_finite());
+	}
+}
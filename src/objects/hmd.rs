@@ -1,4 +1,4 @@
-use super::{ObjectHandle, SpatialRef, input::mouse_pointer::FlatscreenCam};
+use super::{ObjectHandle, SpatialRef, input::mouse_pointer::FlatscreenCam, object_path};
 use crate::{DbusConnection, PreFrameWait, get_time, nodes::spatial::Spatial};
 use bevy::prelude::*;
 use bevy_mod_openxr::{
@@ -24,8 +24,16 @@ impl Plugin for HmdPlugin {
 	}
 }
 
+/// An HMD that stays at the origin, for running headless
+pub struct HeadlessHmdPlugin;
+impl Plugin for HeadlessHmdPlugin {
+	fn build(&self, app: &mut App) {
+		app.add_systems(Startup, setup);
+	}
+}
+
 fn setup(connection: Res<DbusConnection>, mut cmds: Commands) {
-	let (spatial, _spatial_handle) = SpatialRef::create(&connection, "/org/stardustxr/HMD");
+	let (spatial, _spatial_handle) = SpatialRef::create(&connection, &object_path("HMD"));
 	let hmd = Hmd {
 		spatial,
 		_spatial_handle,
@@ -402,7 +402,7 @@ impl MousePointer {
 						.await
 						.ok()?
 						.ok()?;
-					let field_node = EXPORTED_FIELDS.get(&uid)?.upgrade()?;
+					let field_node = EXPORTED_FIELDS.get(uid)?;
 					let field = field_node.get_aspect::<Field>();
 					Some((field, keyboard_proxy))
 				}
@@ -411,7 +411,7 @@ impl MousePointer {
 						.await
 						.ok()?
 						.ok()?;
-					let field_node = EXPORTED_FIELDS.get(&uid)?.upgrade()?;
+					let field_node = EXPORTED_FIELDS.get(uid)?;
 					let field = field_node.get_aspect::<Field>();
 					Some((field, keyboard_proxy))
 				}
@@ -13,7 +13,7 @@ use crate::{
 		input::{INPUT_HANDLER_REGISTRY, InputDataType, InputHandler, InputMethod, Tip},
 		spatial::Spatial,
 	},
-	objects::{AsyncTracked, ObjectHandle, SpatialRef, Tracked},
+	objects::{AsyncTracked, ObjectHandle, SpatialRef, Tracked, bus_name, object_path},
 };
 use bevy::{asset::Handle, ecs::resource::Resource};
 use bevy::{math::Affine3, prelude::*};
@@ -361,7 +361,7 @@ fn setup(instance: Res<OxrInstance>, connection: Res<DbusConnection>, mut cmds:
 		let connection = connection.clone();
 		async move {
 			connection
-				.request_name("org.stardustxr.Controllers")
+				.request_name(bus_name("Controllers").as_str())
 				.await
 				.unwrap();
 		}
@@ -429,11 +429,10 @@ pub struct OxrControllerInput {
 }
 impl OxrControllerInput {
 	fn new(connection: &Connection, side: HandSide) -> Result<Self> {
-		let path = "/org/stardustxr/Controller/".to_string()
-			+ match side {
-				HandSide::Left => "left",
-				HandSide::Right => "right",
-			};
+		let path = object_path(match side {
+			HandSide::Left => "Controller/left",
+			HandSide::Right => "Controller/right",
+		});
 		let (spatial, object_handle) = SpatialRef::create(connection, &path);
 		let tracked = AsyncTracked::new(connection, &path);
 		let tip = InputDataType::Tip(Tip::default());
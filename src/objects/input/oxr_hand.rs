@@ -8,7 +8,7 @@ use crate::nodes::{
 	input::{Hand, InputMethod, Joint},
 	spatial::Spatial,
 };
-use crate::objects::{AsyncTracked, ObjectHandle, SpatialRef, Tracked};
+use crate::objects::{AsyncTracked, ObjectHandle, SpatialRef, Tracked, bus_name, object_path};
 use crate::{BevyMaterial, DbusConnection, ObjectRegistryRes, PreFrameWait, get_time};
 use bevy::pbr::ExtendedMaterial;
 use bevy::prelude::Transform as BevyTransform;
@@ -158,7 +158,7 @@ fn setup(
 		let connection = connection.clone();
 		async move {
 			connection
-				.request_name("org.stardustxr.Hands")
+				.request_name(bus_name("Hands").as_str())
 				.await
 				.unwrap();
 		}
@@ -233,19 +233,17 @@ impl OxrHandInput {
 	) -> Result<Self> {
 		let (palm_spatial, palm_object) = SpatialRef::create(
 			connection,
-			&("/org/stardustxr/Hand/".to_string()
-				+ match side {
-					HandSide::Left => "left",
-					HandSide::Right => "right",
-				} + "/palm"),
+			&object_path(match side {
+				HandSide::Left => "Hand/left/palm",
+				HandSide::Right => "Hand/right/palm",
+			}),
 		);
 		let tracked = AsyncTracked::new(
 			connection,
-			&("/org/stardustxr/Hand/".to_string()
-				+ match side {
-					HandSide::Left => "left",
-					HandSide::Right => "right",
-				}),
+			&object_path(match side {
+				HandSide::Left => "Hand/left",
+				HandSide::Right => "Hand/right",
+			}),
 		);
 		let node = Node::generate(&INTERNAL_CLIENT, false).add_to_scenegraph_owned()?;
 		Spatial::add_to(&node.0, None, Mat4::IDENTITY);
@@ -1,9 +1,11 @@
 #![allow(unused)]
 
 use crate::{
+	STARDUST_INSTANCE,
 	core::client::INTERNAL_CLIENT,
 	nodes::{
 		Node, OwnedNode,
+		export::ExportRules,
 		fields::{EXPORTED_FIELDS, Field, Shape},
 		spatial::{EXPORTED_SPATIALS, Spatial},
 	},
@@ -18,7 +20,7 @@ use play_space::PlaySpaceBounds;
 use stardust_xr_gluon::object_registry::ObjectRegistry;
 use std::{
 	marker::PhantomData,
-	sync::{Arc, atomic::Ordering},
+	sync::{Arc, OnceLock, atomic::Ordering},
 };
 use tokio::{sync::mpsc, task::AbortHandle};
 use zbus::{Connection, interface, object_server::Interface, zvariant::OwnedObjectPath};
@@ -27,6 +29,56 @@ pub mod hmd;
 pub mod input;
 pub mod play_space;
 
+static DBUS_NAMESPACE: OnceLock<Option<String>> = OnceLock::new();
+
+/// Claim the DBus names for this server instance. The first instance on the session bus gets the
+/// plain `org.stardustxr.*` names so clients that don't know about instances keep working, any
+/// others namespace their names and object paths by `STARDUST_INSTANCE`.
+pub async fn claim_dbus_names(connection: &Connection) {
+	let namespace = match connection.request_name("org.stardustxr.HMD").await {
+		Ok(()) => None,
+		Err(zbus::Error::NameTaken) => Some(
+			STARDUST_INSTANCE
+				.get()
+				.unwrap()
+				.replace(|c: char| !c.is_ascii_alphanumeric(), "_"),
+		),
+		Err(e) => panic!("Couldn't request a name on the dbus session: {e}"),
+	};
+	let namespaced = namespace.is_some();
+	DBUS_NAMESPACE
+		.set(namespace)
+		.expect("DBus names were claimed twice");
+	if namespaced {
+		connection
+			.request_name(bus_name("HMD").as_str())
+			.await
+			.expect("Another server is already using the DBus names of this instance");
+	}
+}
+/// e.g. `org.stardustxr` or `org.stardustxr.stardust_1`
+pub fn bus_name_prefix() -> String {
+	match DBUS_NAMESPACE.get().cloned().flatten() {
+		Some(namespace) => format!("org.stardustxr.{namespace}"),
+		None => "org.stardustxr".to_string(),
+	}
+}
+/// e.g. `/org/stardustxr` or `/org/stardustxr/stardust_1`
+pub fn object_path_prefix() -> String {
+	match DBUS_NAMESPACE.get().cloned().flatten() {
+		Some(namespace) => format!("/org/stardustxr/{namespace}"),
+		None => "/org/stardustxr".to_string(),
+	}
+}
+/// The well-known bus name `name` has for this server instance
+pub fn bus_name(name: &str) -> String {
+	format!("{}.{name}", bus_name_prefix())
+}
+/// The object path `path` (relative to `/org/stardustxr`) has for this server instance
+pub fn object_path(path: &str) -> String {
+	format!("{}/{path}", object_path_prefix())
+}
+
 pub struct ObjectHandle<I: Interface>(Connection, OwnedObjectPath, PhantomData<I>);
 
 impl<I: Interface> Clone for ObjectHandle<I> {
@@ -91,8 +143,7 @@ impl SpatialRef {
 	pub fn create(connection: &Connection, path: &str) -> (Arc<Spatial>, ObjectHandle<SpatialRef>) {
 		let node = OwnedNode(Arc::new(Node::generate(&INTERNAL_CLIENT, false)));
 		let spatial = Spatial::add_to(&node.0, None, Mat4::IDENTITY);
-		let uid: u64 = rand::random();
-		EXPORTED_SPATIALS.insert(uid, Arc::downgrade(&node.0));
+		let uid = EXPORTED_SPATIALS.export(&node.0, ExportRules::default()).0;
 
 		tokio::task::spawn({
 			let connection = connection.clone();
@@ -179,8 +230,7 @@ impl FieldRef {
 		let node = OwnedNode(Arc::new(Node::generate(&INTERNAL_CLIENT, false)));
 		Spatial::add_to(&node.0, None, Mat4::IDENTITY);
 		let field = Field::add_to(&node.0, shape).unwrap();
-		let uid: u64 = rand::random();
-		EXPORTED_FIELDS.insert(uid, Arc::downgrade(&node.0));
+		let uid = EXPORTED_FIELDS.export(&node.0, ExportRules::default()).0;
 
 		tokio::task::spawn({
 			let connection = connection.clone();
@@ -1,5 +1,7 @@
-use super::{AsyncTracked, ObjectHandle, SpatialRef, Tracked};
-use crate::{DbusConnection, PreFrameWait, get_time, nodes::spatial::Spatial};
+use super::{AsyncTracked, ObjectHandle, SpatialRef, Tracked, bus_name, object_path};
+use crate::{
+	DbusConnection, PreFrameWait, config::ServerConfig, get_time, nodes::spatial::Spatial,
+};
 use bevy::prelude::*;
 use bevy_mod_openxr::{
 	helper_traits::{ToQuat, ToVec3},
@@ -26,9 +28,9 @@ impl Plugin for PlaySpacePlugin {
 }
 
 fn setup(connection: Res<DbusConnection>, mut cmds: Commands) {
-	let (spatial, spatial_handle) = SpatialRef::create(&connection, "/org/stardustxr/PlaySpace");
+	let (spatial, spatial_handle) = SpatialRef::create(&connection, &object_path("PlaySpace"));
 	// the OpenXR session might not exist quite yet
-	let tracked = AsyncTracked::new(&connection, "/org/stardustxr/PlaySpace");
+	let tracked = AsyncTracked::new(&connection, &object_path("PlaySpace"));
 	let dbus_connection = connection.clone();
 	let play_space_data = Arc::new(RwLock::default());
 	tokio::task::spawn({
@@ -36,7 +38,7 @@ fn setup(connection: Res<DbusConnection>, mut cmds: Commands) {
 		async move {
 			PlaySpaceBounds::create(&dbus_connection, data).await;
 			dbus_connection
-				.request_name("org.stardustxr.PlaySpace")
+				.request_name(bus_name("PlaySpace").as_str())
 				.await
 				.unwrap();
 		}
@@ -82,7 +84,11 @@ fn update(
 
 		play_space
 			.spatial
-			.set_local_transform(Mat4::from_translation(vec3(0.0, -1.65, 0.0)));
+			.set_local_transform(Mat4::from_translation(vec3(
+				0.0,
+				-ServerConfig::get().display.flatscreen_floor_height,
+				0.0,
+			)));
 		return;
 	};
 	let time = get_time(pipelined.is_some(), &state);
@@ -136,7 +142,7 @@ impl PlaySpaceBounds {
 	pub async fn create(connection: &Connection, data: Arc<RwLock<Vec<(f64, f64)>>>) {
 		connection
 			.object_server()
-			.at("/org/stardustxr/PlaySpace", Self(data))
+			.at(object_path("PlaySpace"), Self(data))
 			.await
 			.unwrap();
 	}
@@ -0,0 +1,135 @@
+use rustc_hash::FxHashMap;
+use serde::Deserialize;
+
+/// Variables that only make sense in the session they came from, or that carry secrets.
+/// The ones the server sets itself are regenerated from `connection_env()` on restore.
+const DEFAULT_DENIED: &[&str] = &[
+	"STARDUST_*",
+	"WAYLAND_DISPLAY",
+	"WAYLAND_SOCKET",
+	"FLAT_WAYLAND_DISPLAY",
+	"DISPLAY",
+	"XAUTHORITY",
+	"XDG_CURRENT_DESKTOP",
+	"XDG_SESSION_*",
+	"XDG_SEAT",
+	"XDG_VTNR",
+	"XDG_ACTIVATION_TOKEN",
+	"DESKTOP_STARTUP_ID",
+	"DBUS_SESSION_BUS_ADDRESS",
+	"SSH_AUTH_SOCK",
+	"SSH_AGENT_PID",
+	"GPG_AGENT_INFO",
+	"KRB5CCNAME",
+	"INVOCATION_ID",
+	"JOURNAL_STREAM",
+	"MANAGERPID",
+	"SYSTEMD_EXEC_PID",
+	"AWS_*",
+	"*TOKEN*",
+	"*SECRET*",
+	"*PASSWORD*",
+	"*PASSWD*",
+	"*API_KEY*",
+	"*CREDENTIAL*",
+];
+
+/// Which environment variables of a client get saved with its state, the
+/// `[session.saved_env]` section of `server.toml`. Patterns are matched ignoring case and `*`
+/// matches any number of characters.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EnvFilter {
+	/// If not empty, only variables matching one of these are saved
+	pub allow: Vec<String>,
+	/// Variables matching any of these are never saved, even if allowed. Setting this replaces
+	/// the defaults.
+	pub deny: Vec<String>,
+}
+impl Default for EnvFilter {
+	fn default() -> Self {
+		EnvFilter {
+			allow: Vec::new(),
+			deny: DEFAULT_DENIED.iter().map(ToString::to_string).collect(),
+		}
+	}
+}
+impl EnvFilter {
+	pub fn allows(&self, var: &str) -> bool {
+		(self.allow.is_empty() || self.allow.iter().any(|p| glob_match(p, var)))
+			&& !self.deny.iter().any(|p| glob_match(p, var))
+	}
+	pub fn filter(&self, env: &mut FxHashMap<String, String>) {
+		env.retain(|var, _| self.allows(var));
+	}
+}
+
+fn glob_match(pattern: &str, name: &str) -> bool {
+	let pattern = pattern.to_ascii_uppercase();
+	let name = name.to_ascii_uppercase();
+	let mut parts = pattern.split('*');
+	let first = parts.next().unwrap_or_default();
+	let Some(mut rest) = name.strip_prefix(first) else {
+		return false;
+	};
+	let parts = parts.collect::<Vec<_>>();
+	let Some((last, middle)) = parts.split_last() else {
+		// no wildcards
+		return rest.is_empty();
+	};
+	for part in middle {
+		let Some(index) = rest.find(part) else {
+			return false;
+		};
+		rest = &rest[index + part.len()..];
+	}
+	rest.ends_with(last)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn glob_patterns() {
+		assert!(glob_match("HOME", "HOME"));
+		assert!(!glob_match("HOME", "HOMEDIR"));
+		assert!(glob_match("STARDUST_*", "STARDUST_INSTANCE"));
+		assert!(glob_match("*TOKEN*", "github_token"));
+		assert!(glob_match("*_KEY", "OPENAI_API_KEY"));
+		assert!(!glob_match("*_KEY", "KEYBOARD"));
+		assert!(glob_match("A*B*C", "AXXBYYC"));
+		assert!(!glob_match("A*B*C", "AXXCYYB"));
+	}
+
+	#[test]
+	fn default_filter() {
+		let mut env = FxHashMap::default();
+		for var in [
+			"HOME",
+			"PATH",
+			"LANG",
+			"SSH_AUTH_SOCK",
+			"DBUS_SESSION_BUS_ADDRESS",
+			"STARDUST_STARTUP_TOKEN",
+			"WAYLAND_DISPLAY",
+			"GITHUB_TOKEN",
+		] {
+			env.insert(var.to_string(), String::new());
+		}
+		EnvFilter::default().filter(&mut env);
+		let mut kept = env.keys().map(String::as_str).collect::<Vec<_>>();
+		kept.sort();
+		assert_eq!(kept, ["HOME", "LANG", "PATH"]);
+	}
+
+	#[test]
+	fn allowlist() {
+		let filter = EnvFilter {
+			allow: vec!["LANG".to_string(), "LC_*".to_string()],
+			..Default::default()
+		};
+		assert!(filter.allows("LC_ALL"));
+		assert!(!filter.allows("HOME"));
+	}
+}
@@ -0,0 +1,329 @@
+use super::{GENERATED_SESSION_MARKER, replace_symlink};
+use crate::core::client_state::{ClientStateError, ClientStateParsed};
+use clap::Subcommand;
+use color_eyre::eyre::{Result, bail, eyre};
+use directories::ProjectDirs;
+use std::{
+	ffi::OsStr,
+	path::{Path, PathBuf},
+	time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Names of the symlinks the server maintains in the state directory
+const SESSION_LINKS: [&str; 2] = ["latest", "recovery"];
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum SessionsCommand {
+	/// List saved sessions, newest first
+	List,
+	/// Show the clients saved in a session
+	Show { id: String },
+	/// Delete a saved session
+	Delete { id: String },
+	/// Delete all but the newest sessions the server saved. Renamed sessions are always kept.
+	Prune {
+		#[clap(long, default_value_t = 5)]
+		keep: usize,
+	},
+	/// Give a session a name that can be passed to `--restore`
+	Rename { id: String, name: String },
+}
+impl SessionsCommand {
+	pub fn run(self, project_dirs: Option<&ProjectDirs>) -> Result<()> {
+		let state_dir = project_dirs
+			.and_then(ProjectDirs::state_dir)
+			.ok_or_else(|| eyre!("Unable to find the Stardust state directory"))?;
+		match self {
+			SessionsCommand::List => {
+				let sessions = saved_sessions(state_dir)?;
+				if sessions.is_empty() {
+					println!("No saved sessions in {}", state_dir.display());
+				}
+				for session in sessions {
+					let links = session_links(state_dir, &session.path);
+					println!(
+						"{}{}\t{}\t{}",
+						session.id,
+						if links.is_empty() {
+							String::new()
+						} else {
+							format!(" ({})", links.join(", "))
+						},
+						format_date(session.saved),
+						session.client_names().join(", ")
+					);
+				}
+			}
+			SessionsCommand::Show { id } => {
+				let session = find_session(state_dir, &id)?;
+				println!("Session {}", session.id);
+				println!("Path: {}", session.path.display());
+				println!(
+					"Saved: {} ({})",
+					format_date(session.saved),
+					format_age(session.saved)
+				);
+				for (path, client) in session.clients() {
+					let client = match client {
+						Ok(client) => client,
+						Err(e) => {
+							println!("- {} can't be loaded: {e}", path.display());
+							continue;
+						}
+					};
+					let Some(launch_info) = client.launch_info else {
+						println!("- unknown client (can't be relaunched)");
+						continue;
+					};
+					println!("- {}", launch_info.cmdline.join(" "));
+					println!("  cwd: {}", launch_info.cwd.display());
+					if client.data.is_some() {
+						println!("  has saved data");
+					}
+				}
+			}
+			SessionsCommand::Delete { id } => {
+				let session = find_session(state_dir, &id)?;
+				delete_session(state_dir, &session)?;
+				println!("Deleted session {}", session.id);
+			}
+			SessionsCommand::Prune { keep } => {
+				let sessions = saved_sessions(state_dir)?;
+				for session in sessions
+					.into_iter()
+					.filter(SavedSession::is_generated)
+					.skip(keep)
+				{
+					delete_session(state_dir, &session)?;
+					println!("Deleted session {}", session.id);
+				}
+			}
+			SessionsCommand::Rename { id, name } => {
+				if name.is_empty()
+					|| name.contains('/')
+					|| name.starts_with('.')
+					|| name.starts_with("recovery")
+					|| SESSION_LINKS.contains(&name.as_str())
+				{
+					bail!("\"{name}\" can't be used as a session name");
+				}
+				let new_path = state_dir.join(&name);
+				if new_path.exists() {
+					bail!("A session named \"{name}\" already exists");
+				}
+				let session = find_session(state_dir, &id)?;
+				let links = session_links(state_dir, &session.path);
+				std::fs::rename(&session.path, &new_path)?;
+				// named now, so it has to be kept
+				match std::fs::remove_file(new_path.join(GENERATED_SESSION_MARKER)) {
+					Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+					_ => (),
+				}
+				for link in links {
+					replace_symlink(&new_path, &state_dir.join(link))?;
+				}
+				println!("Renamed session {} to {name}", session.id);
+			}
+		}
+		Ok(())
+	}
+}
+
+struct SavedSession {
+	id: String,
+	path: PathBuf,
+	saved: SystemTime,
+}
+impl SavedSession {
+	/// Saved by the server with a random ID and never renamed. Sessions from before the marker
+	/// existed count as named.
+	fn is_generated(&self) -> bool {
+		std::fs::symlink_metadata(self.path.join(GENERATED_SESSION_MARKER))
+			.is_ok_and(|metadata| metadata.is_file())
+	}
+	fn clients(&self) -> Vec<(PathBuf, Result<ClientStateParsed, ClientStateError>)> {
+		let Ok(entries) = self.path.read_dir() else {
+			return Vec::new();
+		};
+		entries
+			.filter_map(Result::ok)
+			.map(|entry| entry.path())
+			.filter(|path| path.extension() == Some(OsStr::new("toml")))
+			.map(|path| {
+				let client = ClientStateParsed::from_file(&path);
+				(path, client)
+			})
+			.collect()
+	}
+	fn client_names(&self) -> Vec<String> {
+		self.clients()
+			.into_iter()
+			.map(|(_, client)| {
+				let Ok(client) = client else {
+					return "unreadable".to_string();
+				};
+				client
+					.launch_info
+					.and_then(|launch_info| {
+						let exe = launch_info.cmdline.first()?;
+						Some(exe.rsplit('/').next().unwrap_or(exe).to_string())
+					})
+					.unwrap_or_else(|| "unknown".to_string())
+			})
+			.collect()
+	}
+}
+
+/// All session directories, newest first. Recovery snapshots only show up through the
+/// `recovery` link.
+fn saved_sessions(state_dir: &Path) -> Result<Vec<SavedSession>> {
+	let Ok(entries) = state_dir.read_dir() else {
+		return Ok(Vec::new());
+	};
+	let mut sessions = entries
+		.filter_map(Result::ok)
+		.filter(|entry| entry.file_type().is_ok_and(|t| t.is_dir()))
+		.filter_map(|entry| {
+			let id = entry.file_name().to_str()?.to_string();
+			Some(SavedSession {
+				saved: entry.metadata().ok()?.modified().ok()?,
+				path: entry.path(),
+				id,
+			})
+		})
+		.filter(|session| {
+			!session.id.starts_with("recovery-")
+				|| !session_links(state_dir, &session.path).is_empty()
+		})
+		.collect::<Vec<_>>();
+	sessions.sort_by_key(|session| std::cmp::Reverse(session.saved));
+	Ok(sessions)
+}
+
+/// Look up a session by its ID, name, or one of the links like `latest`
+fn find_session(state_dir: &Path, id: &str) -> Result<SavedSession> {
+	if id.is_empty() || id == "." || id == ".." || id.contains('/') {
+		bail!("Invalid session ID \"{id}\"");
+	}
+	let path = state_dir.join(id);
+	let path = if SESSION_LINKS.contains(&id) {
+		std::fs::read_link(&path).map_err(|_| eyre!("There is no {id} session"))?
+	} else {
+		path
+	};
+	let metadata = std::fs::symlink_metadata(&path)
+		.map_err(|_| eyre!("Couldn't find a session called \"{id}\""))?;
+	// sessions get deleted with everything in them, so this has to be one
+	if !metadata.is_dir() || !is_session_dir(state_dir, &path) {
+		bail!("\"{id}\" is not a session");
+	}
+	Ok(SavedSession {
+		id: path
+			.file_name()
+			.and_then(OsStr::to_str)
+			.unwrap_or(id)
+			.to_string(),
+		saved: metadata.modified()?,
+		path,
+	})
+}
+
+/// Whether `path` is a directory directly inside the state directory, after resolving
+/// symlinks and `..`
+fn is_session_dir(state_dir: &Path, path: &Path) -> bool {
+	let (Ok(state_dir), Ok(path)) = (state_dir.canonicalize(), path.canonicalize()) else {
+		return false;
+	};
+	path.parent() == Some(state_dir.as_path())
+}
+
+fn delete_session(state_dir: &Path, session: &SavedSession) -> Result<()> {
+	for link in session_links(state_dir, &session.path) {
+		std::fs::remove_file(state_dir.join(link))?;
+	}
+	std::fs::remove_dir_all(&session.path)?;
+	Ok(())
+}
+
+/// Which of the server maintained links point at this session
+fn session_links(state_dir: &Path, session_path: &Path) -> Vec<&'static str> {
+	SESSION_LINKS
+		.into_iter()
+		.filter(|link| {
+			std::fs::read_link(state_dir.join(link)).is_ok_and(|target| target == session_path)
+		})
+		.collect()
+}
+
+/// The UTC date and time, without pulling in a date library for it
+fn format_date(time: SystemTime) -> String {
+	let secs = time
+		.duration_since(UNIX_EPOCH)
+		.unwrap_or_default()
+		.as_secs();
+	let (days, secs_of_day) = ((secs / 86400) as i64, secs % 86400);
+	// days since the epoch to a civil date, Howard Hinnant's `civil_from_days`
+	let z = days + 719_468;
+	let era = z.div_euclid(146_097);
+	let day_of_era = z.rem_euclid(146_097);
+	let year_of_era =
+		(day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+	let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+	let shifted_month = (5 * day_of_year + 2) / 153;
+	let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+	let month = if shifted_month < 10 {
+		shifted_month + 3
+	} else {
+		shifted_month - 9
+	};
+	let year = era * 400 + year_of_era + i64::from(month <= 2);
+	format!(
+		"{year:04}-{month:02}-{day:02} {:02}:{:02} UTC",
+		secs_of_day / 3600,
+		secs_of_day % 3600 / 60
+	)
+}
+
+fn format_age(time: SystemTime) -> String {
+	let age = time.elapsed().unwrap_or(Duration::ZERO).as_secs();
+	match age {
+		0..60 => "just now".to_string(),
+		60..3600 => format!("{} minutes ago", age / 60),
+		3600..86400 => format!("{} hours ago", age / 3600),
+		_ => format!("{} days ago", age / 86400),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn sessions_stay_in_state_dir() {
+		let root = std::env::temp_dir().join(format!("stardust-sessions-{}", std::process::id()));
+		let state_dir = root.join("state");
+		let _ = std::fs::remove_dir_all(&root);
+		std::fs::create_dir_all(state_dir.join("session")).unwrap();
+		std::fs::create_dir_all(root.join("elsewhere")).unwrap();
+		std::os::unix::fs::symlink(state_dir.join("session"), state_dir.join("latest")).unwrap();
+		std::os::unix::fs::symlink(&root, state_dir.join("recovery")).unwrap();
+		std::os::unix::fs::symlink(root.join("elsewhere"), state_dir.join("escape")).unwrap();
+
+		assert!(find_session(&state_dir, "session").is_ok());
+		assert_eq!(find_session(&state_dir, "latest").unwrap().id, "session");
+		for id in [".", "..", "", "session/..", "recovery", "escape"] {
+			assert!(find_session(&state_dir, id).is_err(), "{id}");
+		}
+
+		std::fs::remove_dir_all(root).unwrap();
+	}
+
+	#[test]
+	fn dates() {
+		let date = |secs| format_date(UNIX_EPOCH + Duration::from_secs(secs));
+		assert_eq!(date(0), "1970-01-01 00:00 UTC");
+		assert_eq!(date(951_782_400), "2000-02-29 00:00 UTC");
+		assert_eq!(date(1_700_000_000), "2023-11-14 22:13 UTC");
+		assert_eq!(date(4_107_542_400), "2100-03-01 00:00 UTC");
+	}
+}
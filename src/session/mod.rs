@@ -0,0 +1,282 @@
+mod env;
+mod manage;
+mod startup;
+
+pub use env::EnvFilter;
+pub use manage::SessionsCommand;
+
+use crate::config::ServerConfig;
+use crate::core::client::{AUTOSAVE_DISCONNECTED, CLIENTS};
+use crate::core::client_state::ClientStateParsed;
+use crate::objects::{bus_name_prefix, object_path_prefix};
+#[cfg(feature = "wayland")]
+use crate::wayland::WAYLAND_DISPLAY;
+use crate::{CliArgs, STARDUST_INSTANCE};
+use directories::ProjectDirs;
+use rustc_hash::FxHashMap;
+use serde::Deserialize;
+use startup::StartupConfig;
+use std::ffi::OsStr;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::time::Duration;
+use tokio::task::{JoinSet, LocalSet};
+use tracing::{error, info, warn};
+
+/// The `[session]` section of `server.toml`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SessionConfig {
+	/// Seconds between snapshots of the recovery session, 0 disables autosaving
+	pub autosave_interval_secs: u64,
+	/// Which environment variables get saved to relaunch clients with
+	pub saved_env: EnvFilter,
+	/// Same as `--debug`
+	pub debug_launched_clients: bool,
+	/// Same as `--execute-startup-script`
+	pub startup_script: Option<PathBuf>,
+	/// How long clients get to save and disconnect when the server shuts down, in seconds
+	pub shutdown_deadline_secs: f32,
+}
+impl Default for SessionConfig {
+	fn default() -> Self {
+		SessionConfig {
+			autosave_interval_secs: 30,
+			saved_env: EnvFilter::default(),
+			debug_launched_clients: false,
+			startup_script: None,
+			shutdown_deadline_secs: 2.0,
+		}
+	}
+}
+
+/// File in a session directory saved with a random ID, so pruning never touches sessions the
+/// user named
+const GENERATED_SESSION_MARKER: &str = ".generated";
+
+pub async fn save_session(project_dirs: &ProjectDirs) {
+	let session_id = nanoid::nanoid!();
+	let state_dir = project_dirs.state_dir().unwrap();
+	let session_dir = state_dir.join(&session_id);
+	if let Err(e) = save_clients(&session_dir).await {
+		error!("Couldn't save session: {e}");
+		return;
+	}
+	if let Err(e) = std::fs::write(session_dir.join(GENERATED_SESSION_MARKER), []) {
+		warn!("Couldn't mark the session as generated, it won't be pruned: {e}");
+	}
+	if let Err(e) = replace_symlink(&session_dir, &state_dir.join("latest")) {
+		error!("Couldn't link the latest session: {e}");
+	}
+	info!("Session ID for restore is {session_id}");
+}
+
+/// Keep a rolling snapshot of the session at `recovery` in the state directory so it can be
+/// restored with `--restore recovery` after a crash. Never returns.
+pub async fn autosave(project_dirs: Option<&ProjectDirs>) {
+	let interval_secs = ServerConfig::get().session.autosave_interval_secs;
+	let Some(state_dir) = project_dirs.and_then(ProjectDirs::state_dir) else {
+		return std::future::pending().await;
+	};
+	if interval_secs == 0 {
+		return std::future::pending().await;
+	}
+	let period = Duration::from_secs(interval_secs);
+	let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+	// whether the last snapshot this run had any clients in it
+	let mut saved_clients = false;
+	loop {
+		tokio::select! {
+			_ = interval.tick() => (),
+			_ = AUTOSAVE_DISCONNECTED.notified() => (),
+		}
+		let has_clients = CLIENTS
+			.get_vec()
+			.iter()
+			.any(|client| client.message_sender_handle.is_some());
+		// an empty snapshot is only needed once the last client has closed, before that it
+		// would replace the one from a previous run that hasn't been restored yet
+		if !has_clients && !saved_clients {
+			continue;
+		}
+		match save_recovery(state_dir).await {
+			Ok(()) => saved_clients = has_clients,
+			Err(e) => warn!("Couldn't autosave the session: {e}"),
+		}
+	}
+}
+async fn save_recovery(state_dir: &Path) -> std::io::Result<()> {
+	let snapshot_name = format!("recovery-{}", nanoid::nanoid!());
+	save_clients(&state_dir.join(&snapshot_name)).await?;
+	// swapping the symlink is atomic, so `recovery` always points at a complete snapshot
+	replace_symlink(&state_dir.join(&snapshot_name), &state_dir.join("recovery"))?;
+	for entry in state_dir.read_dir()?.filter_map(Result::ok) {
+		let name = entry.file_name();
+		let name = name.to_string_lossy();
+		if name.starts_with("recovery-") && name != snapshot_name {
+			let _ = std::fs::remove_dir_all(entry.path());
+		}
+	}
+	Ok(())
+}
+
+/// Save the state of every client into a new directory at `session_dir`
+async fn save_clients(session_dir: &Path) -> std::io::Result<()> {
+	std::fs::create_dir_all(session_dir)?;
+	let local_set = LocalSet::new();
+	for client in CLIENTS.get_vec() {
+		let session_dir = session_dir.to_path_buf();
+		local_set.spawn_local(async move {
+			tokio::select! {
+				biased;
+				s = client.save_state() => {
+					if let Some(s) = s && let Err(e) = s.to_file(&session_dir) {
+						warn!(?client, "Couldn't save client state: {e}");
+					}
+				},
+				_ = tokio::time::sleep(Duration::from_millis(100)) => (),
+			}
+		});
+	}
+	local_set.await;
+	Ok(())
+}
+
+fn replace_symlink(target: &Path, link: &Path) -> std::io::Result<()> {
+	let temp_link = link.with_extension(format!("{}.tmp", nanoid::nanoid!()));
+	std::os::unix::fs::symlink(target, &temp_link)?;
+	std::fs::rename(temp_link, link)
+}
+
+/// Everything launched when the server started
+#[derive(Default)]
+pub struct StartupClients {
+	children: Vec<Child>,
+	supervisors: JoinSet<()>,
+}
+impl StartupClients {
+	pub fn kill(mut self) {
+		for child in &mut self.children {
+			let _ = child.kill();
+		}
+		// supervised clients get killed when their supervisor is dropped
+		self.supervisors.abort_all();
+	}
+}
+
+pub fn launch_start(cli_args: &CliArgs, project_dirs: &ProjectDirs) -> StartupClients {
+	let debug_launched_clients = cli_args.debug_launched_clients;
+	let children = match (&cli_args.restore, &cli_args.startup_script) {
+		(Some(session_id), _) => restore_session(
+			&project_dirs.state_dir().unwrap().join(session_id),
+			debug_launched_clients,
+		),
+		(None, Some(startup_script)) => run_script(
+			&startup_script.clone().canonicalize().unwrap_or_default(),
+			debug_launched_clients,
+		),
+		(None, None) => {
+			if let Some(startup_config) =
+				StartupConfig::load(&project_dirs.config_dir().join("startup.toml"))
+			{
+				let mut supervisors = JoinSet::new();
+				for client in startup_config.clients {
+					supervisors.spawn(startup::supervise_client(client, debug_launched_clients));
+				}
+				return StartupClients {
+					children: Vec::new(),
+					supervisors,
+				};
+			}
+			run_script(
+				&project_dirs.config_dir().join("startup"),
+				debug_launched_clients,
+			)
+		}
+	};
+	StartupClients {
+		children,
+		supervisors: JoinSet::new(),
+	}
+}
+
+pub fn restore_session(session_dir: &Path, debug_launched_clients: bool) -> Vec<Child> {
+	let Ok(clients) = session_dir.read_dir() else {
+		error!(session_dir = ?session_dir.display(), "Couldn't read session to restore");
+		return Vec::new();
+	};
+	let mut failed = Vec::new();
+	let children = clients
+		.filter_map(Result::ok)
+		.map(|c| c.path())
+		.filter(|path| path.extension() == Some(OsStr::new("toml")))
+		.filter_map(|path| {
+			ClientStateParsed::from_file(&path)
+				.inspect_err(|e| error!(path = ?path.display(), "Couldn't load client state: {e}"))
+				.map_err(|_| failed.push(path))
+				.ok()
+		})
+		.filter_map(ClientStateParsed::launch_command)
+		.filter_map(|c| run_client(c, debug_launched_clients))
+		.collect();
+	if !failed.is_empty() {
+		warn!(
+			?failed,
+			"{} client states in the session couldn't be restored",
+			failed.len()
+		);
+	}
+	children
+}
+
+pub fn run_script(script_path: &Path, debug_launched_clients: bool) -> Vec<Child> {
+	let _ = std::fs::set_permissions(script_path, std::fs::Permissions::from_mode(0o755));
+	let startup_command = Command::new(script_path);
+	run_client(startup_command, debug_launched_clients)
+		.map(|c| vec![c])
+		.unwrap_or_default()
+}
+
+pub fn run_client(mut command: Command, debug_launched_clients: bool) -> Option<Child> {
+	command.stdin(Stdio::null());
+	if !debug_launched_clients {
+		command.stdout(Stdio::null());
+		command.stderr(Stdio::null());
+	}
+	for (var, value) in connection_env() {
+		command.env(var, value);
+	}
+	let child = command.spawn().ok()?;
+	Some(child)
+}
+
+pub fn connection_env() -> FxHashMap<String, String> {
+	let mut env: FxHashMap<String, String> = FxHashMap::default();
+	env.insert(
+		"STARDUST_INSTANCE".to_string(),
+		STARDUST_INSTANCE.get().unwrap().clone(),
+	);
+	env.insert("XDG_CURRENT_DESKTOP".to_string(), "Stardust".to_string());
+	env.insert("STARDUST_DBUS_NAME_PREFIX".to_string(), bus_name_prefix());
+	env.insert(
+		"STARDUST_DBUS_PATH_PREFIX".to_string(),
+		object_path_prefix(),
+	);
+
+	if let Some(flat_wayland_display) = std::env::var_os("WAYLAND_DISPLAY") {
+		env.insert(
+			"FLAT_WAYLAND_DISPLAY".to_string(),
+			flat_wayland_display.to_string_lossy().to_string(),
+		);
+	}
+	#[cfg(feature = "wayland")]
+	{
+		env.insert(
+			"WAYLAND_DISPLAY".to_string(),
+			WAYLAND_DISPLAY.get().unwrap().to_string_lossy().to_string(),
+		);
+		env.insert("XDG_SESSION_TYPE".to_string(), "wayland".to_string());
+	}
+	env
+}
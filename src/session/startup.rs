@@ -0,0 +1,206 @@
+use super::connection_env;
+use crate::core::client_state::ClientStateParsed;
+use glam::{EulerRot, Mat4, Quat, Vec3};
+use rustc_hash::FxHashMap;
+use serde::Deserialize;
+use std::{
+	path::{Path, PathBuf},
+	process::Stdio,
+	time::{Duration, Instant},
+};
+use tokio::process::Command;
+use tracing::{error, info, warn};
+
+/// Clients to launch when the server starts, read from `startup.toml` in the stardust config
+/// directory. Used instead of the `startup` script when it exists.
+///
+/// ```toml
+/// [[client]]
+/// command = "flatland"
+/// restart = "on_failure"
+///
+/// [[client]]
+/// command = "/opt/clients/clock"
+/// args = ["--seconds"]
+/// env = { RUST_LOG = "info" }
+/// transform = { position = [0.0, 1.5, -0.5], rotation = [0.0, 180.0, 0.0] }
+/// restart = "always"
+/// backoff = { initial_secs = 2.0, max_secs = 30.0 }
+/// ```
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StartupConfig {
+	#[serde(rename = "client")]
+	pub clients: Vec<StartupClient>,
+}
+impl StartupConfig {
+	/// `None` if there's no startup config or it's invalid
+	pub fn load(path: &Path) -> Option<Self> {
+		let config_string = std::fs::read_to_string(path).ok()?;
+		toml::from_str(&config_string)
+			.inspect_err(|e| error!(path = ?path.display(), "Invalid startup config: {e}"))
+			.ok()
+	}
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StartupClient {
+	/// The program to run, looked up in `PATH` if it isn't a path
+	pub command: String,
+	#[serde(default)]
+	pub args: Vec<String>,
+	/// Defaults to the server's working directory
+	pub cwd: Option<PathBuf>,
+	/// Set on top of the server's environment
+	#[serde(default)]
+	pub env: FxHashMap<String, String>,
+	/// Where to put the client's root when it connects
+	pub transform: Option<StartupTransform>,
+	#[serde(default)]
+	pub restart: RestartPolicy,
+	#[serde(default)]
+	pub backoff: Backoff,
+}
+impl StartupClient {
+	fn command(&self, startup_token: Option<&str>, debug_launched_clients: bool) -> Command {
+		let mut command = Command::new(&self.command);
+		command.args(&self.args);
+		if let Some(cwd) = &self.cwd {
+			command.current_dir(cwd);
+		}
+		command.envs(&self.env);
+		command.envs(connection_env());
+		if let Some(startup_token) = startup_token {
+			command.env("STARDUST_STARTUP_TOKEN", startup_token);
+		}
+		command.stdin(Stdio::null());
+		if !debug_launched_clients {
+			command.stdout(Stdio::null());
+			command.stderr(Stdio::null());
+		}
+		command.kill_on_drop(true);
+		command
+	}
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StartupTransform {
+	pub position: Vec3,
+	/// Euler angles in degrees, applied in yaw (Y), pitch (X), roll (Z) order
+	pub rotation: Vec3,
+}
+impl StartupTransform {
+	fn to_mat4(&self) -> Mat4 {
+		let rotation = Quat::from_euler(
+			EulerRot::YXZ,
+			self.rotation.y.to_radians(),
+			self.rotation.x.to_radians(),
+			self.rotation.z.to_radians(),
+		);
+		Mat4::from_rotation_translation(rotation, self.position)
+	}
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RestartPolicy {
+	/// Run the client once
+	#[default]
+	Never,
+	/// Restart the client when it exits with an error or gets killed
+	OnFailure,
+	/// Restart the client whenever it exits
+	Always,
+}
+
+/// How long to wait between restarts. The delay doubles with every restart, and goes back to
+/// `initial_secs` once the client stays up for longer than `max_secs`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Backoff {
+	pub initial_secs: f32,
+	pub max_secs: f32,
+}
+impl Backoff {
+	/// The initial and maximum delay, using the defaults for values that aren't valid durations
+	fn durations(&self, command: &str) -> (Duration, Duration) {
+		let default = Backoff::default();
+		let duration = |name, secs: f32, default_secs| {
+			Duration::try_from_secs_f32(secs.max(0.0))
+				.inspect_err(|e| {
+					warn!(
+						command,
+						"Invalid backoff {name} of {secs}, using {default_secs}: {e}"
+					)
+				})
+				.unwrap_or(Duration::from_secs_f32(default_secs))
+		};
+		(
+			duration("initial_secs", self.initial_secs, default.initial_secs),
+			duration("max_secs", self.max_secs, default.max_secs),
+		)
+	}
+}
+impl Default for Backoff {
+	fn default() -> Self {
+		Backoff {
+			initial_secs: 1.0,
+			max_secs: 60.0,
+		}
+	}
+}
+
+/// Run a startup client and keep restarting it according to its restart policy.
+/// The client is killed when this is dropped.
+pub async fn supervise_client(client: StartupClient, debug_launched_clients: bool) {
+	let startup_token = client.transform.as_ref().map(|transform| {
+		ClientStateParsed {
+			root: transform.to_mat4(),
+			..Default::default()
+		}
+		.token()
+	});
+	let (initial_backoff, max_backoff) = client.backoff.durations(&client.command);
+	let mut backoff = initial_backoff;
+	loop {
+		let started = Instant::now();
+		let status = match client
+			.command(startup_token.as_deref(), debug_launched_clients)
+			.spawn()
+		{
+			Ok(mut child) => child.wait().await,
+			Err(e) => Err(e),
+		};
+		let failed = match &status {
+			Ok(status) => {
+				info!(command = client.command, %status, "Startup client exited");
+				!status.success()
+			}
+			Err(e) => {
+				warn!(command = client.command, "Couldn't run startup client: {e}");
+				true
+			}
+		};
+		let restart = match client.restart {
+			RestartPolicy::Never => false,
+			RestartPolicy::OnFailure => failed,
+			RestartPolicy::Always => true,
+		};
+		if !restart {
+			return;
+		}
+		// it's not crash looping if it stayed up for a while
+		if started.elapsed() > max_backoff {
+			backoff = initial_backoff;
+		}
+		warn!(
+			command = client.command,
+			"Restarting startup client in {:.1} seconds",
+			backoff.as_secs_f32()
+		);
+		tokio::time::sleep(backoff).await;
+		backoff = backoff.saturating_mul(2).min(max_backoff);
+	}
+}